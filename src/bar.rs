@@ -0,0 +1,142 @@
+use std::mem;
+use std::ptr;
+
+use x11::xft::XftDraw;
+use x11::xlib::{self, Window};
+
+use crate::config::STYLE;
+use crate::layout::Rect;
+use crate::state;
+use crate::style::{BarMonitors, BarPosition};
+use crate::widgets::widget_window;
+use crate::wm::WindowExt;
+use crate::active_workspace;
+
+/* one bar, on one monitor or across the whole display, see STYLE.bar.monitors */
+pub struct BarWindow {
+    pub window: Window,
+    pub trigger: Window /* input-only strip at the screen edge revealing it while auto-hidden */,
+    pub rect: Rect,
+    pub buffer: xlib::Pixmap /* the bar is drawn here first, then copied onto `window` */,
+    pub buffer_size: (u32, u32),
+    pub xft_draw: *mut XftDraw,
+    pub widget_rects: Vec<Rect> /* where the widgets were drawn last on this bar, in WIDGETS order */
+}
+
+impl BarWindow {
+    pub fn new(dpy: *mut xlib::Display) -> BarWindow {
+        let trigger = unsafe {
+            let mut wa: xlib::XSetWindowAttributes = mem::zeroed();
+            wa.override_redirect = xlib::True;
+            wa.event_mask = xlib::EnterWindowMask;
+            xlib::XCreateWindow(dpy, xlib::XDefaultRootWindow(dpy), 0, 0, 1, 1, 0, 0,
+                xlib::InputOnly as u32, xlib::CopyFromParent as *mut xlib::Visual,
+                xlib::CWOverrideRedirect | xlib::CWEventMask, &mut wa)
+        };
+        BarWindow {
+            window: widget_window(dpy),
+            trigger,
+            rect: (0, 0, 0, 0),
+            buffer: 0,
+            buffer_size: (0, 0),
+            xft_draw: ptr::null_mut(),
+            widget_rects: Vec::new()
+        }
+    }
+}
+
+impl state::State<'_> {
+    /* where the bars go, on the top or bottom edge of their monitors or of the whole display */
+    pub fn bar_rects(&mut self) -> Vec<Rect> {
+        let monitors = self.monitors();
+        let mut areas: Vec<Rect> = Vec::new();
+        match STYLE.bar.monitors {
+            BarMonitors::Display => areas.push(unsafe {
+                (0, 0, xlib::XDisplayWidth(self.dpy, self.screen) as u32, xlib::XDisplayHeight(self.dpy, self.screen) as u32)
+            }),
+            BarMonitors::All => areas = monitors.clone(),
+            BarMonitors::Only(only) => for monitor in only.iter().filter_map(|no| monitors.get(*no)) {
+                if !areas.contains(monitor) { areas.push(*monitor); }
+            }
+        }
+        if areas.is_empty() { areas.push(monitors[0]); }
+
+        let height = self.bar_height;
+        areas.into_iter().map(|area| match STYLE.bar.position {
+            BarPosition::Top => (area.0, area.1, area.2, height),
+            BarPosition::Bottom => (area.0, area.1 + area.3 as i32 - height as i32, area.2, height)
+        }).collect()
+    }
+
+    /* the bars tiled windows are kept clear of - none while hidden, an auto-hidden bar overlaps the windows */
+    pub fn reserved_bar_rects(&self) -> Vec<Rect> {
+        if !self.bar_visible || STYLE.bar.auto_hide { return Vec::new() }
+        self.bars.iter().map(|bar| bar.rect).collect()
+    }
+
+    /* the bar a window belongs to, None for anything else */
+    pub fn bar_of(&self, window: Window) -> Option<usize> {
+        self.bars.iter().position(|bar| bar.window == window)
+    }
+
+    pub fn is_bar_trigger(&self, window: Window) -> bool {
+        self.bars.iter().any(|bar| bar.trigger == window)
+    }
+
+    /* moves the bars and their auto-hide triggers into place and maps whichever should be seen */
+    pub fn place_bar(&mut self) {
+        self.bar_height = self.measure_bar();
+        let rects = self.bar_rects();
+        /* the monitors don't change while running, neither does the number of bars */
+        while self.bars.len() < rects.len() {
+            self.bars.push(BarWindow::new(self.dpy));
+        }
+
+        let shown = self.bar_visible && (!STYLE.bar.auto_hide || self.bar_revealed);
+        let armed = self.bar_visible && STYLE.bar.auto_hide && !self.bar_revealed;
+        for (no, rect) in rects.into_iter().enumerate() {
+            self.bars[no].rect = rect;
+            self.fit_bar_buffer(no);
+            let edge = match STYLE.bar.position {
+                BarPosition::Top => (rect.0, rect.1, rect.2, 1),
+                BarPosition::Bottom => (rect.0, rect.1 + rect.3 as i32 - 1, rect.2, 1)
+            };
+            let (window, trigger) = (self.bars[no].window, self.bars[no].trigger);
+            unsafe {
+                xlib::XMoveResizeWindow(self.dpy, window, rect.0, rect.1, rect.2, rect.3);
+                xlib::XMoveResizeWindow(self.dpy, trigger, edge.0, edge.1, edge.2, edge.3);
+                if shown { xlib::XMapRaised(self.dpy, window); }
+                if armed { xlib::XMapRaised(self.dpy, trigger); }
+            }
+            if !shown { window.do_unmap(self); }
+            if !armed { trigger.do_unmap(self); }
+        }
+    }
+
+    pub fn toggle_bar(&mut self) {
+        self.bar_visible = !self.bar_visible;
+        self.bar_revealed = false;
+        self.place_bar();
+        self.retile();
+    }
+
+    /* the pointer hit a trigger at the screen edge, the bars show together */
+    pub fn reveal_bar(&mut self) {
+        if !STYLE.bar.auto_hide || self.bar_revealed { return }
+        /* a fullscreen window keeps the bar under it */
+        let fullscreen = match &active_workspace!(self).custom {
+            Some(custom) => active_workspace!(self).windows.iter().any(|w| custom.fullscreen_windows.contains(w) && !custom.minimised.contains(w)),
+            None => false
+        };
+        if fullscreen { return }
+        self.bar_revealed = true;
+        self.place_bar();
+    }
+
+    /* the pointer left a revealed bar */
+    pub fn conceal_bar(&mut self) {
+        if !STYLE.bar.auto_hide || !self.bar_revealed || self.picker.is_some() { return }
+        self.bar_revealed = false;
+        self.place_bar();
+    }
+}
@@ -1,30 +1,39 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::process::Command;
 use std::sync::Arc;
-use std::time::Duration;
 
+use serde_json::json;
 use x11::keysym;
 use x11::xlib;
 
 use crate::add_widget;
+use crate::rules::{Geometry, Rule};
+use crate::scratchpad::Scratchpad;
+use crate::script::Script;
+use crate::tray::TrayIcons;
 use crate::state::WIDGETS;
 use crate::state::{self, Keybinding, Mousemotion, KEYBINDINGS, MOUSEMOTIONS};
-use crate::style::Paddings;
+use crate::layout::Slot;
+use crate::mode::ModeIndicator;
+use crate::style::{Bar, BarMonitors, BarPosition, Paddings, Titlebar};
 use crate::style::{ColorScheme, ColorSchemes, Style};
 use crate::widgets::Ctx;
-use crate::widgets::Stats;
-use crate::widgets::{TopBar, Widget};
-use crate::wm::WindowExt;
+use crate::stats::{Battery, Clock, Cpu, Disk, Load, Memory, Network, Swap, Temperature, Uptime};
+use crate::widgets::{TaskList, TopBar, Widget};
+use crate::wm::{Dir, WindowExt};
 use crate::{active_workspace, active_workspace_wins, set_keybinding, set_mousemotion, set_spaces, spawn_with_shell, wm};
 
 macro_rules! toggle_active_window_prop {
-    ($state: expr, $set: ident) => {
+    ($state: expr, $set: ident, $event: expr) => {
+        if !$state.active_in_space() { return }
         if let Some(custom) = &mut active_workspace!($state).custom {
             if let Some(_)  = custom.$set.iter().position(|x| *x == $state.active.window) {
                 custom.$set.remove(&$state.active.window);
             } else {
                 custom.$set.insert($state.active.window);
             }
+            let (window, enabled) = ($state.active.window, custom.$set.contains(&$state.active.window));
+            $state.emit($event, |_| json!({ "id": window, "enabled": enabled }));
             $state.retile();
         }
     };
@@ -41,30 +50,90 @@ pub static STYLE: Style = Style {
             fg: "#ffff00",
             bg: "#ffffff",
             border: "#ffff00"
+       },
+       dimmed: ColorScheme {
+            fg: "#8aa3a2",
+            bg: "#ffffff",
+            border: "#c5caa9"
+       },
+       urgent: ColorScheme {
+            fg: "#ffffff",
+            bg: "#c0392b",
+            border: "#c0392b"
        }
     },
     border_thickness: 5,
-    useless_gap: 5,
+    outer_gap: Paddings {
+        top: 5,
+        bottom: 5,
+        left: 5,
+        right: 5
+    },
+    inner_gap: 5,
+    smart_gaps: true,
+    smart_borders: true,
     paddings: Paddings {
-        top: 20,
+        top: 0,
         bottom: 0,
         left: 0,
         right: 0
+    },
+    titlebar: Titlebar {
+        height: 20,
+        font: "Noto Sans-10",
+        by_default: false
+    },
+    bar: Bar {
+        padding: 3,
+        fallback_fonts: &["Noto Sans CJK JP-10", "Symbols Nerd Font-10"],
+        position: BarPosition::Top,
+        monitors: BarMonitors::Display,
+        auto_hide: false,
+        separator: " "
     }
 };
 
-pub static WIDGET_REFRESH: Duration = Duration::from_secs(15);
+/* monitor layout as (x, y, width, height), leave empty to use what Xinerama reports (a single monitor covering */
+/* the whole display without it) - needed for the monitor features when libXinerama isn't installed            */
+pub static MONITORS: &[(i32, i32, u32, u32)] = &[];
+
+/* applied to new windows, all the matching ones in order */
+pub static RULES: &[Rule] = &[
+    Rule { class: Some("firefox"), workspace: Some(1), ..Rule::ANY },
+    Rule { class: Some("pavucontrol"), floating: Some(true), geometry: Some(Geometry::Centered(900, 600)), ..Rule::ANY },
+    Rule { class: Some("mpv"), fullscreen: Some(true), monitor: Some(1), ..Rule::ANY },
+    Rule { window_type: Some("dialog"), floating: Some(true), ..Rule::ANY },
+];
+
+/* toggled by name, the window is recognised by its WM_CLASS class */
+pub static SCRATCHPADS: &[Scratchpad] = &[
+    Scratchpad { name: "term", command: "alacritty", args: &["--class", "scratchpad"], class: "scratchpad", size: (1200, 700) },
+];
 
 const MODKEY: u32 = xlib::Mod4Mask;
 const MODKEY_SHIFT: u32 = MODKEY |  xlib::ShiftMask;
 const MODKEY_CTRL: u32 = MODKEY |  xlib::ControlMask;
+const MODKEY_CTRL_SHIFT: u32 = MODKEY_CTRL |  xlib::ShiftMask;
 
 /* your private config goes here */
 pub fn make(state: &mut state::State){
     /* widgets */
     {
-        add_widget!(state, TopBar, "Noto Sans CJK JP-12");
-        add_widget!(state, Stats, "Noto Sans-12");
+        add_widget!(state, Slot::Left, TopBar, "Noto Sans CJK JP-12");
+        add_widget!(state, Slot::Left, ModeIndicator, "Noto Sans-12");
+        add_widget!(state, Slot::Center, TaskList, "Noto Sans CJK JP-12");
+        add_widget!(state, Slot::Right, Script { command: "xkb-switch -W", interval: None }, "Noto Sans-12");
+        add_widget!(state, Slot::Right, Network, "Noto Sans-12");
+        add_widget!(state, Slot::Right, Disk, "Noto Sans-12");
+        add_widget!(state, Slot::Right, Temperature, "Noto Sans-12");
+        add_widget!(state, Slot::Right, Battery, "Noto Sans-12");
+        add_widget!(state, Slot::Right, Uptime, "Noto Sans-12");
+        add_widget!(state, Slot::Right, Load { warning: Some(4.0) }, "Noto Sans-12");
+        add_widget!(state, Slot::Right, Memory { on_click: Some("alacritty -e htop --sort-key PERCENT_MEM") }, "Noto Sans-12");
+        add_widget!(state, Slot::Right, Swap, "Noto Sans-12");
+        add_widget!(state, Slot::Right, Cpu { on_click: Some("alacritty -e htop") }, "Noto Sans-12");
+        add_widget!(state, Slot::Right, Clock { format: "%a %d %b %H:%M:%S" }, "Noto Sans-12");
+        add_widget!(state, Slot::Right, TrayIcons);
     }
 
     /* mouse motion */
@@ -81,11 +150,16 @@ pub fn make(state: &mut state::State){
     {
         set_keybinding!( modkey: MODKEY, callback: |_| {spawn_with_shell!("alacritty");}, key: keysym::XK_Return );
         set_keybinding!( modkey: MODKEY, callback: |_| {spawn_with_shell!("dmenu_run");}, key: keysym::XK_r );
+        set_keybinding!( modkey: MODKEY, callback: |state| {state.toggle_scratchpad("term");}, key: keysym::XK_grave );
         set_keybinding!( modkey: MODKEY, callback: |state| {state.focus_next();}, key: keysym::XK_j );
         set_keybinding!( modkey: MODKEY, callback: |state| {state.focus_previous();}, key: keysym::XK_k );
-        set_keybinding!( modkey: MODKEY, callback: |state| {toggle_active_window_prop!(state, fullscreen_windows);}, key: keysym::XK_f );
+        set_keybinding!( modkey: MODKEY, callback: |state| {state.toggle_fullscreen();}, key: keysym::XK_f );
+        set_keybinding!( modkey: MODKEY, callback: |state| {state.toggle_decoration();}, key: keysym::XK_t );
+        set_keybinding!( modkey: MODKEY, callback: |state| {state.toggle_bar();}, key: keysym::XK_b );
         set_keybinding!( modkey: MODKEY, callback: |state| {state.separator_modify(40)}, key: keysym::XK_l );
         set_keybinding!( modkey: MODKEY, callback: |state| {state.separator_modify(-40)}, key: keysym::XK_h );
+        set_keybinding!( modkey: MODKEY, callback: |state| {state.gap_modify(5)}, key: keysym::XK_equal );
+        set_keybinding!( modkey: MODKEY, callback: |state| {state.gap_modify(-5)}, key: keysym::XK_minus );
         set_keybinding!( modkey: MODKEY, callback: |state| { state.next_workspace(); }, key: keysym::XK_Right );
         set_keybinding!( modkey: MODKEY, callback: |state| { state.prev_workspace(); }, key: keysym::XK_Left );
         set_keybinding!( modkey: MODKEY, callback: |state| { state.goto_workspace(0); }, key: keysym::XK_1 );
@@ -97,13 +171,45 @@ pub fn make(state: &mut state::State){
         set_keybinding!( modkey: MODKEY_SHIFT, callback: |state| { state.send_active_window_to_workspace(2); }, key: keysym::XK_3 );
         set_keybinding!( modkey: MODKEY_SHIFT, callback: |state| { state.send_active_window_to_workspace(3); }, key: keysym::XK_4 );
         set_keybinding!( modkey: MODKEY_SHIFT, callback: |state| {state.close_active();}, key: keysym::XK_c );
+        set_keybinding!( modkey: MODKEY_SHIFT, callback: |state| {state.zoom();}, key: keysym::XK_Return );
+        set_keybinding!( modkey: MODKEY, callback: |state| {state.minimise_active();}, key: keysym::XK_n );
+        set_keybinding!( modkey: MODKEY_CTRL, callback: |state| {state.restore_last();}, key: keysym::XK_n );
+        set_keybinding!( modkey: MODKEY_SHIFT, callback: |state| {state.open_restore_picker();}, key: keysym::XK_n );
+        set_keybinding!( modkey: MODKEY_SHIFT, callback: |state| {state.move_in_stack(1);}, key: keysym::XK_j );
+        set_keybinding!( modkey: MODKEY_SHIFT, callback: |state| {state.move_in_stack(-1);}, key: keysym::XK_k );
+        set_keybinding!( modkey: MODKEY_CTRL, callback: |state| {state.rotate_stack(true);}, key: keysym::XK_j );
+        set_keybinding!( modkey: MODKEY_CTRL, callback: |state| {state.rotate_stack(false);}, key: keysym::XK_k );
         set_keybinding!( modkey: MODKEY_SHIFT, callback: |state| {state.active_floating_move(0, 40);}, key: keysym::XK_Down );
         set_keybinding!( modkey: MODKEY_SHIFT, callback: |state| {state.active_floating_move(0, -40);}, key: keysym::XK_Up );
         set_keybinding!( modkey: MODKEY_SHIFT, callback: |state| {state.active_floating_move(-40, 0);}, key: keysym::XK_Left );
         set_keybinding!( modkey: MODKEY_SHIFT, callback: |state| {state.active_floating_move(40, 0);}, key: keysym::XK_Right );
         set_keybinding!( modkey: MODKEY_SHIFT, callback: |state| {state.active_floating_resize(40, 40);}, key: keysym::XK_plus );
         set_keybinding!( modkey: MODKEY_SHIFT, callback: |state| {state.active_floating_resize(-40, -40);}, key: keysym::XK_minus );
-        set_keybinding!( modkey: MODKEY_CTRL, callback: |state| {toggle_active_window_prop!(state, floating_windows);}, key: keysym::XK_space );
+        set_keybinding!( modkey: MODKEY_CTRL, callback: |state| {state.toggle_floating();}, key: keysym::XK_space );
+        set_keybinding!( modkey: MODKEY_CTRL, callback: |state| {state.focus_direction(Dir::Left);}, key: keysym::XK_Left );
+        set_keybinding!( modkey: MODKEY_CTRL, callback: |state| {state.focus_direction(Dir::Right);}, key: keysym::XK_Right );
+        set_keybinding!( modkey: MODKEY_CTRL, callback: |state| {state.focus_direction(Dir::Up);}, key: keysym::XK_Up );
+        set_keybinding!( modkey: MODKEY_CTRL, callback: |state| {state.focus_direction(Dir::Down);}, key: keysym::XK_Down );
+        set_keybinding!( modkey: MODKEY_CTRL_SHIFT, callback: |state| {state.swap_direction(Dir::Left);}, key: keysym::XK_Left );
+        set_keybinding!( modkey: MODKEY_CTRL_SHIFT, callback: |state| {state.swap_direction(Dir::Right);}, key: keysym::XK_Right );
+        set_keybinding!( modkey: MODKEY_CTRL_SHIFT, callback: |state| {state.swap_direction(Dir::Up);}, key: keysym::XK_Up );
+        set_keybinding!( modkey: MODKEY_CTRL_SHIFT, callback: |state| {state.swap_direction(Dir::Down);}, key: keysym::XK_Down );
+    }
+
+    /* modes and chords, Escape leaves them */
+    {
+        set_keybinding!( modkey: MODKEY, callback: |state| {state.enter_chord("workspace");}, key: keysym::XK_w );
+        set_keybinding!( mode: "workspace", modkey: 0, callback: |state| { state.goto_workspace(0); }, key: keysym::XK_1 );
+        set_keybinding!( mode: "workspace", modkey: 0, callback: |state| { state.goto_workspace(1); }, key: keysym::XK_2 );
+        set_keybinding!( mode: "workspace", modkey: 0, callback: |state| { state.goto_workspace(2); }, key: keysym::XK_3 );
+        set_keybinding!( mode: "workspace", modkey: 0, callback: |state| { state.goto_workspace(3); }, key: keysym::XK_4 );
+
+        set_keybinding!( modkey: MODKEY, callback: |state| {state.enter_mode("resize");}, key: keysym::XK_s );
+        set_keybinding!( mode: "resize", modkey: 0, callback: |state| {state.separator_modify(-40)}, key: keysym::XK_h );
+        set_keybinding!( mode: "resize", modkey: 0, callback: |state| {state.separator_modify(40)}, key: keysym::XK_l );
+        set_keybinding!( mode: "resize", modkey: 0, callback: |state| {state.active_floating_resize(0, 40)}, key: keysym::XK_j );
+        set_keybinding!( mode: "resize", modkey: 0, callback: |state| {state.active_floating_resize(0, -40)}, key: keysym::XK_k );
+        set_keybinding!( mode: "resize", modkey: 0, callback: |state| {state.exit_mode()}, key: keysym::XK_Return );
     }
 
     /* startup apps */
@@ -122,8 +228,12 @@ pub fn make(state: &mut state::State){
         for space in state.workspaces.iter_mut() {
             space.custom = Some(CustomData {
                 separator: screen_width/2,
+                outer_gap: STYLE.outer_gap,
+                inner_gap: STYLE.inner_gap,
                 fullscreen_windows: HashSet::new(),
                 floating_windows: HashSet::new(),
+                borders: HashMap::new(),
+                minimised: Vec::new(),
                 rightclick_grab_origin: (0,0),
                 rightclick_grab_window: 0,
                 rightclick_grabbing: false,
@@ -137,8 +247,12 @@ pub fn make(state: &mut state::State){
 
 pub struct CustomData {
     pub separator: u32 /* used by cascade_autotiling */,
+    pub outer_gap: Paddings /* adjustable at runtime */,
+    pub inner_gap: u32 /* adjustable at runtime */,
     pub fullscreen_windows: HashSet<xlib::Window>,
     pub floating_windows: HashSet<xlib::Window>,
+    pub borders: HashMap<xlib::Window, u32> /* set by rules */,
+    pub minimised: Vec<xlib::Window> /* unmapped but kept in the Space, the latest last */,
     pub rightclick_grab_origin: (i32, i32),
     pub rightclick_grab_window: xlib::Window,
     pub rightclick_grabbing: bool,
@@ -176,7 +290,9 @@ impl state::State<'_> {
 
         if let Some(_) = &mut active_workspace!(self).custom {
             for window in active_workspace!(self).windows.iter(){
-                if active_workspace!(self).custom.as_ref().unwrap().fullscreen_windows.contains(window) {
+                if active_workspace!(self).custom.as_ref().unwrap().minimised.contains(window) {
+                    continue;
+                } else if active_workspace!(self).custom.as_ref().unwrap().fullscreen_windows.contains(window) {
                     fullscreen_windows.push(*window);
                     continue;
                 } else if is_floating!(self, window) {
@@ -193,22 +309,46 @@ impl state::State<'_> {
         self.cascade_autotiling(tiled_windows);     
         self.draw_floating_windows(&floating_windows);
         self.draw_fullscreen_windows(&fullscreen_windows);
+        self.emit_focus();
     }
 
-    fn separator_modify(&mut self, modifier: i32) {
+    pub fn toggle_floating(&mut self) {
+        toggle_active_window_prop!(self, floating_windows, "floating");
+    }
+
+    pub fn toggle_fullscreen(&mut self) {
+        toggle_active_window_prop!(self, fullscreen_windows, "fullscreen");
+    }
+
+    pub fn separator_modify(&mut self, modifier: i32) {
         if let Some(custom ) = &mut active_workspace!(self).custom {
             custom.separator = (custom.separator as i32 + modifier).clamp(100, 1760) as u32;
             self.retile();
+            self.emit_layout();
         }
     }
     
-    fn draw_fullscreen_windows(&mut self, windows: &Vec<xlib::Window>){
-        let screen_width: u32 = unsafe{xlib::XDisplayWidth(self.dpy, self.screen) as u32};
-        let screen_height = unsafe{xlib::XDisplayHeight(self.dpy, self.screen) as u32};
+    pub fn gap_modify(&mut self, modifier: i32) {
+        if let Some(custom ) = &mut active_workspace!(self).custom {
+            let grow = |gap: u32| (gap as i32 + modifier).clamp(0, 100) as u32;
+            custom.inner_gap = grow(custom.inner_gap);
+            custom.outer_gap = Paddings {
+                top: grow(custom.outer_gap.top),
+                bottom: grow(custom.outer_gap.bottom),
+                left: grow(custom.outer_gap.left),
+                right: grow(custom.outer_gap.right)
+            };
+            self.retile();
+            self.emit_layout();
+        }
+    }
     
+    fn draw_fullscreen_windows(&mut self, windows: &Vec<xlib::Window>){
         for window in windows {
-            unsafe { xlib::XRaiseWindow(self.dpy, *window) };       
-            window.do_map(self, (0, 0, screen_width, screen_height));
+            unsafe { xlib::XRaiseWindow(self.dpy, window.outer(self)) };       
+            let rect = window.get_rect(self);
+            let monitor = self.monitor_of(rect);
+            window.do_map(self, monitor);
         }
     }
 
@@ -325,6 +465,7 @@ impl state::State<'_> {
         if is_floating!(self, &window) || is_fullscreen!(self, &window) { return }
         active_workspace_wins!(self).swap(maybe_ix1.unwrap(), maybe_ix2.unwrap());
         self.retile();
+        self.emit_layout();
     }
 }
 
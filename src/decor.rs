@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::mem;
+
+use x11::xft;
+use x11::xlib::{self, Window};
+
+use crate::active_workspace_wins;
+use crate::config::STYLE;
+use crate::state;
+use crate::widgets::{draw_text, ellipsize, text_baseline, text_width_px};
+use crate::wm::WindowExt;
+
+/* server-side decorations - a managed window reparented into a frame drawing its title bar */
+pub struct Decorations {
+    pub font: *mut xft::XftFont,
+    pub frames: HashMap<Window, Frame>
+}
+
+pub struct Frame {
+    pub window: Window,
+    pub xft_draw: *mut xft::XftDraw
+}
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum TitlebarButton {
+    Float,
+    Fullscreen,
+    Close
+}
+
+/* right-aligned, last one at the very edge */
+const BUTTONS: [(TitlebarButton, &str); 3] = [
+    (TitlebarButton::Float, "~"),
+    (TitlebarButton::Fullscreen, "+"),
+    (TitlebarButton::Close, "x")
+];
+
+impl Decorations {
+    pub fn new(dpy: *mut xlib::Display, screen: i32) -> Decorations {
+        let font = CString::new(STYLE.titlebar.font).unwrap();
+        Decorations {
+            font: unsafe { xft::XftFontOpenName(dpy, screen, font.as_ptr()) },
+            frames: HashMap::new()
+        }
+    }
+
+    pub fn is_frame(&self, window: Window) -> bool {
+        self.frames.values().any(|f| f.window == window)
+    }
+
+    /* the managed window for a frame, the window itself otherwise */
+    pub fn client_of(&self, window: Window) -> Window {
+        self.frames.iter().find(|(_, f)| f.window == window).map(|(c, _)| *c).unwrap_or(window)
+    }
+}
+
+impl state::State<'_> {
+    pub fn decorate(&mut self, window: Window) {
+        if self.decor.frames.contains_key(&window) { return }
+        let mut wa: xlib::XWindowAttributes = unsafe { mem::zeroed() };
+        if unsafe { xlib::XGetWindowAttributes(self.dpy, window, &mut wa) } == 0 { return }
+
+        unsafe {
+            let mut swa: xlib::XSetWindowAttributes = mem::zeroed();
+            swa.background_pixel = self.colors.normal.bg.pixel;
+            swa.event_mask = xlib::ExposureMask | xlib::ButtonPressMask | xlib::EnterWindowMask;
+
+            let frame = xlib::XCreateWindow(self.dpy, self.root,
+                wa.x, wa.y, wa.width as u32, wa.height as u32 + STYLE.titlebar.height, 0,
+                xlib::CopyFromParent, xlib::CopyFromParent as u32, xlib::CopyFromParent as *mut xlib::Visual,
+                xlib::CWBackPixel | xlib::CWEventMask, &mut swa);
+
+            if wa.map_state != xlib::IsUnmapped { self.ignore_unmap.insert(window); }
+            xlib::XAddToSaveSet(self.dpy, window);
+            xlib::XSetWindowBorderWidth(self.dpy, window, 0);
+            xlib::XReparentWindow(self.dpy, window, frame, 0, STYLE.titlebar.height as i32);
+
+            let xft_draw = xft::XftDrawCreate(self.dpy, frame,
+                xlib::XDefaultVisual(self.dpy, self.screen), xlib::XDefaultColormap(self.dpy, self.screen));
+            self.decor.frames.insert(window, Frame { window: frame, xft_draw });
+        }
+    }
+
+    pub fn undecorate(&mut self, window: Window) {
+        let frame = match self.decor.frames.remove(&window) {
+            Some(frame) => frame,
+            None => return
+        };
+        let mut wa: xlib::XWindowAttributes = unsafe { mem::zeroed() };
+        unsafe {
+            xlib::XGetWindowAttributes(self.dpy, frame.window, &mut wa);
+            let mut client_wa: xlib::XWindowAttributes = mem::zeroed();
+            if xlib::XGetWindowAttributes(self.dpy, window, &mut client_wa) != 0 && client_wa.map_state == xlib::IsViewable {
+                self.ignore_unmap.insert(window);
+            }
+            xlib::XReparentWindow(self.dpy, window, self.root, wa.x, wa.y);
+            xlib::XRemoveFromSaveSet(self.dpy, window);
+            xft::XftDrawDestroy(frame.xft_draw);
+            xlib::XDestroyWindow(self.dpy, frame.window);
+        }
+    }
+
+    pub fn toggle_decoration(&mut self) {
+        let window = self.active.window;
+        if !active_workspace_wins!(self).contains(&window) { return }
+        if self.decor.frames.contains_key(&window) {
+            self.undecorate(window);
+        } else {
+            self.decorate(window);
+        }
+        self.retile();
+    }
+
+    pub fn draw_titlebar(&mut self, window: Window) {
+        let (frame, xft_draw) = match self.decor.frames.get(&window) {
+            Some(frame) => (frame.window, frame.xft_draw),
+            None => return
+        };
+        let width = frame.get_rect(self).2;
+        let height = STYLE.titlebar.height;
+        let title = window.get_title(self);
+        let font = self.decor.font;
+        let baseline = text_baseline(font, height);
+        let buttons_width = (BUTTONS.len() as u32 * height) as i32;
+        let pad = (height / 4) as i32;
+        let title = ellipsize(self, font, &title, width as i32 - buttons_width - pad * 2);
+        let label_pads: Vec<i32> = BUTTONS.iter().map(|(_, label)| (height as i32 - text_width_px(self, font, label)) / 2).collect();
+
+        let (bgcol, fgcol) = if window == self.active.window {
+            (self.colors.selected.border, self.colors.normal.bg)
+        } else {
+            (self.colors.normal.border, self.colors.normal.bg)
+        };
+        unsafe { xft::XftDrawRect(xft_draw, &bgcol, 0, 0, width, height) };
+        draw_text(self, xft_draw, fgcol, font, pad, baseline, &title);
+
+        for (i, (_, label)) in BUTTONS.iter().enumerate() {
+            let x = width as i32 - buttons_width + i as i32 * height as i32;
+            draw_text(self, xft_draw, fgcol, font, x + label_pads[i], baseline, label);
+        }
+    }
+
+    pub fn titlebar_click(&mut self, frame: Window, button: u32, x: i32) {
+        let window = self.decor.client_of(frame);
+        if window == frame || button != xlib::Button1 { return }
+        let width = frame.get_rect(self).2 as i32;
+        let height = STYLE.titlebar.height as i32;
+        let ix = (x - (width - BUTTONS.len() as i32 * height)).div_euclid(height);
+
+        self.focus(window);
+        if x < width - BUTTONS.len() as i32 * height || ix >= BUTTONS.len() as i32 {
+            self.retile();
+            return;
+        }
+        match BUTTONS[ix as usize].0 {
+            TitlebarButton::Float => self.toggle_floating(),
+            TitlebarButton::Fullscreen => self.toggle_fullscreen(),
+            TitlebarButton::Close => self.close_active()
+        }
+    }
+}
@@ -1,10 +1,14 @@
 use std::mem;
 
-use x11::xlib::{self, EnterWindowMask, False, PointerMotionMask, StructureNotifyMask, XGetWindowAttributes, XKeycodeToKeysym, XSelectInput, XSync, XWindowAttributes};
+use serde_json::json;
+use x11::xlib::{self, EnterWindowMask, False, PointerMotionMask, PropertyChangeMask, StructureNotifyMask, XGetWindowAttributes, XKeycodeToKeysym, XSelectInput, XSync, XWindowAttributes};
 
 //use crate::init::widget_window;
+use crate::config::STYLE;
+use crate::rules::{self, WindowProps};
 use crate::state::MOUSEMOTIONS;
 use crate::widgets::Ctx;
+use crate::wm::WindowExt;
 use crate::{active_workspace_wins, state::{State, KEYBINDINGS}, };
 
 
@@ -27,12 +31,15 @@ pub fn handle(state: &mut State, ev: xlib::XEvent){
         xlib::KeyPress => callback!(state, key, ev),
         xlib::DestroyNotify => callback!(state, destroy_window, ev),
         xlib::EnterNotify => callback!(state, crossing, ev),
+        xlib::LeaveNotify => callback!(state, leave, crossing, ev),
         xlib::ButtonPress => callback!(state, button_pressed, button, ev),
         xlib::ButtonRelease => callback!(state, button_released, button, ev),
         xlib::MotionNotify => callback!(state, motion, ev),
         xlib::UnmapNotify => callback!(state, unmap, ev),
         xlib::ConfigureNotify => callback!(state, configure_request, ev),
         xlib::Expose => callback!(state, expose, ev),
+        xlib::PropertyNotify => callback!(state, property, ev),
+        xlib::ClientMessage => callback!(state, client_message, client_message, ev),
         _ => println!("xroagwem: unhandled event")
     }
 }
@@ -41,31 +48,64 @@ fn map_request(state: &mut State, ev: xlib::XMapRequestEvent) {
     let mut wa : XWindowAttributes = unsafe { mem::zeroed() };
     if( unsafe { XGetWindowAttributes(state.dpy, ev.window, &mut wa) } == 0) { return };
 
-    unsafe { XSelectInput(state.dpy, ev.window, EnterWindowMask | PointerMotionMask | StructureNotifyMask ) };
+    unsafe { XSelectInput(state.dpy, ev.window, EnterWindowMask | PointerMotionMask | StructureNotifyMask | PropertyChangeMask ) };
 
-    state.focus(ev.window);
-    active_workspace_wins!(state).push(ev.window);
+    if state.adopt_scratchpad(ev.window) { return }
+    let rule = rules::resolve(&WindowProps::of(state, ev.window));
+    if rule.titlebar.unwrap_or(STYLE.titlebar.by_default) { state.decorate(ev.window); }
+    let workspace = state.apply_rule(ev.window, &rule);
+    state.emit("window", |state| json!({ "change": "mapped", "workspace": workspace, "window": state.dump_window(workspace, ev.window) }));
+    if workspace != state.active.workspace { return }
+    if rule.focus.unwrap_or(true) { state.focus(ev.window); }
     state.retile();
     unsafe {XSync(state.dpy, False)};
 }   
 
-fn expose(state: &mut State, __: xlib::XExposeEvent) { 
-    state.draw_widgets(Ctx::Expose);
+fn expose(state: &mut State, ev: xlib::XExposeEvent) { 
+    if state.decor.is_frame(ev.window) {
+        let window = state.decor.client_of(ev.window);
+        state.draw_titlebar(window);
+        return;
+    }
+    if let Some(bar) = state.bar_of(ev.window) {
+        /* the off-screen copy is up to date, nothing to redraw */
+        state.present_bar(bar, (ev.x, ev.y, ev.width as u32, ev.height as u32));
+    }
+}
+
+fn property(state: &mut State, ev: xlib::XPropertyEvent) {
+    if ev.atom == xlib::XA_WM_NAME || ev.atom == state.atom("_NET_WM_NAME") {
+        state.draw_titlebar(ev.window);
+        if active_workspace_wins!(state).contains(&ev.window) { state.draw_widgets(Ctx::Retile); }
+        state.emit("title", |state| json!({ "id": ev.window, "title": ev.window.get_title(state) }));
+    }
 }
 
 fn configure_request(_: &mut State, __: xlib::XConfigureRequestEvent) { }
 
-fn destroy_window(_: &mut State, __: xlib::XDestroyWindowEvent) {
-    /* WARNING: there's some issue with XDestroyWindowEvent.window                    */
-    /* it seems to be getting incorrect window value (not the actually destroyed one) */
-    /* so for now using unmap (but need to be sure unmap is called AFTER              */
-    /* switching active_workspace for changing workspaces                             */
-    /* otherwise all windows will commit suicide)                                     */
+fn client_message(state: &mut State, ev: xlib::XClientMessageEvent) {
+    state.tray_message(&ev);
+}
 
+fn destroy_window(state: &mut State, ev: xlib::XDestroyWindowEvent) {
+    /* .window is the destroyed window, .event the one the notification was selected on (it or root) */
+    /* mapped clients are dropped on their unmap already, this catches the ones that weren't mapped   */
+    /* when they went away - minimised or on another workspace                                        */
+    if ev.event != ev.window { return }
+    state.undock(ev.window);
+    state.forget_window(ev.window);
 }
 
 fn unmap(state: &mut State, ev: xlib::XUnmapEvent) { 
+    /* clients select StructureNotify and root SubstructureNotify, so each unmap arrives twice - */
+    /* only the client's own copy counts, the one from root would undo ignore_unmap             */
+    if ev.event != ev.window { return }
+    if state.ignore_unmap.remove(&ev.window) { return }
+    if state.is_tray_icon(ev.window) { return }
+    state.scratchpads.retain(|_, w| *w != ev.window);
+    state.undecorate(ev.window);
     active_workspace_wins!(state).retain(|x| *x != ev.window);
+    state.emit("window", |_| json!({ "change": "unmapped", "id": ev.window }));
     if ev.window == state.active.window {
         state.focus_next();
     }
@@ -74,8 +114,17 @@ fn unmap(state: &mut State, ev: xlib::XUnmapEvent) {
 
 fn key(state: &mut State, ev: xlib::XKeyEvent) {
     let keysym = unsafe { XKeycodeToKeysym(state.dpy, ev.keycode as u8, 0) } as u32;
+    if state.picker.is_some() {
+        state.picker_key(keysym);
+        return;
+    }
+    let mdky = state.clean_mask(ev.state);
+    if state.mode.is_some() {
+        state.mode_key(keysym, mdky);
+        return;
+    }
     if let Some(binding) = unsafe { KEYBINDINGS.iter() }.find(
-        |x| x.key == keysym && x.mdky ==  ev.state
+        |x| x.mode.is_none() && x.key == keysym && x.mdky == mdky
     ) {
         (binding.callback)(state);
     }
@@ -93,7 +142,7 @@ macro_rules! mm_invoke_callback {
 
     ($state: expr, $ty: ident, $ev: expr, $win: expr) => {
         for mm in unsafe{&MOUSEMOTIONS.$ty} {
-            if mm.button != $ev.button || (mm.mdky & $ev.state) == 0 {
+            if mm.button != $ev.button || mm.mdky != $state.clean_mask($ev.state) {
                 continue;
             }
             (mm.callback)($state, ($ev.x_root, $ev.y_root), $win);
@@ -102,7 +151,7 @@ macro_rules! mm_invoke_callback {
 
     ($state: expr, $ty: ident, $ev: expr, $win:expr, nobutton) => {
         for mm in unsafe{&MOUSEMOTIONS.$ty} {
-            if (mm.mdky & $ev.state) == 0 {
+            if mm.mdky != $state.clean_mask($ev.state) {
                 continue;
             }
             (mm.callback)($state, ($ev.x_root, $ev.y_root), $win);
@@ -111,13 +160,33 @@ macro_rules! mm_invoke_callback {
 }
 
 fn crossing(state: &mut State, ev: xlib::XCrossingEvent) {
-    mm_invoke_callback!(state, on_cross, ev, ev.window, nobutton);
-    if ev.window == state.root { return };
+    if state.is_bar_trigger(ev.window) {
+        state.reveal_bar();
+        return;
+    }
+    let window = state.decor.client_of(ev.window);
+    mm_invoke_callback!(state, on_cross, ev, window, nobutton);
+    if window == state.root { return };
     if state.active.focus_locked { return };
-    state.focus(ev.window);
+    state.focus(window);
     state.retile();
 }
 
-fn button_pressed(state: &mut State, ev: xlib::XButtonEvent){ mm_invoke_callback!(state, on_press, ev); }
+fn leave(state: &mut State, ev: xlib::XCrossingEvent) {
+    /* moving onto a tray icon isn't leaving the bar */
+    if state.bar_of(ev.window).is_some() && ev.detail != xlib::NotifyInferior { state.conceal_bar(); }
+}
+
+fn button_pressed(state: &mut State, ev: xlib::XButtonEvent){
+    if let Some(bar) = state.bar_of(ev.window) {
+        state.bar_click(bar, ev.button, (ev.x, ev.y));
+        return;
+    }
+    if state.decor.is_frame(ev.window) {
+        state.titlebar_click(ev.window, ev.button, ev.x);
+        return;
+    }
+    mm_invoke_callback!(state, on_press, ev);
+}
 fn button_released(state: &mut State, ev: xlib::XButtonEvent){ mm_invoke_callback!(state, on_release, ev); }
 fn motion(state: &mut State, ev: xlib::XMotionEvent){ mm_invoke_callback!(state, on_move, ev, nobutton); }
\ No newline at end of file
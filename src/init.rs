@@ -1,11 +1,15 @@
-use std::mem;
+use std::collections::{HashMap, HashSet};
+use std::{mem, ptr, slice};
 
+use x11::keysym;
 use x11::xlib::{ButtonPressMask, ButtonReleaseMask, PointerMotionMask, CWCursor, CWEventMask, GrabModeAsync, True, XChangeWindowAttributes, XGrabButton, XGrabKey, XSetWindowAttributes};
 use x11::xlib::{self, False, XSync};
 
 use crate::config::STYLE;
+use crate::decor::Decorations;
+use crate::layout::Rect;
 use crate::state::{Active, Cursor, State, KEYBINDINGS, MOUSEMOTIONS};
-use crate::widgets::widget_window;
+use crate::widgets::{bar_gc, open_font};
 
 use super::error;
 use super::state;
@@ -31,7 +35,9 @@ pub fn setup(dpy: &mut xlib::Display) -> state::State {
     {
         let screen =  unsafe { xlib::XDefaultScreen(dpy) };
         let root: u64 = unsafe { xlib::XRootWindow(dpy, screen) };
-        let (draw, xft_draw) = widget_window(dpy);
+        let gc = bar_gc(dpy);
+        let numlock_mask = numlock_mask(dpy);
+        let detected_monitors = detect_monitors(dpy);
 
         state = state::State {
             screen: screen,
@@ -48,13 +54,36 @@ pub fn setup(dpy: &mut xlib::Display) -> state::State {
                 window: root,
                 focus_locked: false
             },
-            draw: draw,
-            xft_draw: xft_draw,
+            bars: Vec::new(),
+            bar: 0,
+            bar_visible: true,
+            bar_revealed: false,
+            bar_height: 0,
+            gc,
+            xft_draw: ptr::null_mut(),
+            fallback_fonts: Vec::new(),
+            decor: Decorations::new(dpy, screen),
+            scratchpads: HashMap::new(),
+            ignore_unmap: HashSet::new(),
+            picker: None,
+            tray: None,
+            ipc: None,
+            mode: None,
+            numlock_mask,
+            detected_monitors,
             dpy: dpy
         };
     }
     
     state.colors = STYLE.colors.to_xft(&mut state);
+    for name in STYLE.bar.fallback_fonts {
+        let font = open_font(&mut state, name);
+        if font.is_null() {
+            println!("xroagwem: warning - cannot open fallback font {}", name);
+        } else {
+            state.fallback_fonts.push(font);
+        }
+    }
 
     unsafe {
         XChangeWindowAttributes(state.dpy, state.root, CWEventMask | CWCursor,  &mut XSetWindowAttributes {
@@ -90,16 +119,86 @@ pub fn setup(dpy: &mut xlib::Display) -> state::State {
     state
 }
 
+#[repr(C)]
+struct XineramaScreenInfo {
+    screen_number: i32,
+    x_org: i16,
+    y_org: i16,
+    width: i16,
+    height: i16
+}
+
+/* the monitors Xinerama reports, empty if it's off or libXinerama isn't installed        */
+/* the library is loaded at runtime so building doesn't need its headers or linker symlink */
+pub fn detect_monitors(dpy: *mut xlib::Display) -> Vec<Rect> {
+    type IsActive = unsafe extern "C" fn(*mut xlib::Display) -> i32;
+    type QueryScreens = unsafe extern "C" fn(*mut xlib::Display, *mut i32) -> *mut XineramaScreenInfo;
+    unsafe {
+        let lib = libc::dlopen(c"libXinerama.so.1".as_ptr(), libc::RTLD_LAZY);
+        if lib.is_null() { return Vec::new() }
+        let is_active = libc::dlsym(lib, c"XineramaIsActive".as_ptr());
+        let query_screens = libc::dlsym(lib, c"XineramaQueryScreens".as_ptr());
+        let mut monitors = Vec::new();
+        if !is_active.is_null() && !query_screens.is_null() && mem::transmute::<*mut libc::c_void, IsActive>(is_active)(dpy) != 0 {
+            let mut count = 0;
+            let screens = mem::transmute::<*mut libc::c_void, QueryScreens>(query_screens)(dpy, &mut count);
+            if !screens.is_null() {
+                for screen in slice::from_raw_parts(screens, count as usize) {
+                    let rect = (screen.x_org as i32, screen.y_org as i32, screen.width as u32, screen.height as u32);
+                    /* cloned outputs show up once each */
+                    if !monitors.contains(&rect) { monitors.push(rect); }
+                }
+                xlib::XFree(screens as *mut _);
+            }
+        }
+        libc::dlclose(lib);
+        monitors
+    }
+}
+
+/* the modifier bit NumLock sits on, it differs between keymaps */
+pub fn numlock_mask(dpy: *mut xlib::Display) -> u32 {
+    unsafe {
+        let numlock = xlib::XKeysymToKeycode(dpy, keysym::XK_Num_Lock as u64);
+        let modmap = xlib::XGetModifierMapping(dpy);
+        if modmap.is_null() { return 0 }
+        let per_modifier = (*modmap).max_keypermod as usize;
+        let keycodes = slice::from_raw_parts((*modmap).modifiermap, 8 * per_modifier);
+        let mask = keycodes.iter()
+            .position(|keycode| numlock != 0 && *keycode == numlock)
+            .map(|ix| 1 << (ix / per_modifier))
+            .unwrap_or(0);
+        xlib::XFreeModifiermap(modmap);
+        mask
+    }
+}
+
+impl State<'_> {
+    /* the modifiers a binding can ask for, without the lock and button bits */
+    pub fn clean_mask(&self, mask: u32) -> u32 {
+        mask & !(self.numlock_mask | xlib::LockMask)
+            & (xlib::ShiftMask | xlib::ControlMask | xlib::Mod1Mask | xlib::Mod2Mask | xlib::Mod3Mask | xlib::Mod4Mask | xlib::Mod5Mask)
+    }
+
+    /* every combination of CapsLock and NumLock, grabs are issued for each so the locks don't get in the way */
+    pub fn lock_masks(&self) -> [u32; 4] {
+        [0, xlib::LockMask, self.numlock_mask, self.numlock_mask | xlib::LockMask]
+    }
+}
+
 pub fn setup_keybindings(state: &mut State){
-    for binding in unsafe { KEYBINDINGS.iter() } {
+    /* bindings of modes are matched while the whole keyboard is grabbed */
+    for binding in unsafe { KEYBINDINGS.iter() }.filter(|binding| binding.mode.is_none()) {
         unsafe {
             let keycode = xlib::XKeysymToKeycode(state.dpy, binding.key as u64);
 
-            XGrabKey(
-            state.dpy, keycode as i32, 
-            binding.mdky, state.root, 
-            True, GrabModeAsync, 
-            GrabModeAsync);
+            for lock in state.lock_masks() {
+                XGrabKey(
+                state.dpy, keycode as i32, 
+                binding.mdky | lock, state.root, 
+                True, GrabModeAsync, 
+                GrabModeAsync);
+            }
         }
     }
 }
@@ -107,19 +206,21 @@ pub fn setup_keybindings(state: &mut State){
 macro_rules! mousemotion_grab {
     ($state: expr, $ty: ident) => {
         for mm in unsafe { MOUSEMOTIONS.$ty.iter() } {
-            unsafe {
-                XGrabButton(
-                    $state.dpy,
-                    mm.button,
-                    mm.mdky,
-                    $state.root,
-                    True,
-                    (ButtonPressMask | ButtonReleaseMask | PointerMotionMask) as u32,
-                    GrabModeAsync,
-                    GrabModeAsync,
-                    0,
-                    0
-                );
+            for lock in $state.lock_masks() {
+                unsafe {
+                    XGrabButton(
+                        $state.dpy,
+                        mm.button,
+                        mm.mdky | lock,
+                        $state.root,
+                        True,
+                        (ButtonPressMask | ButtonReleaseMask | PointerMotionMask) as u32,
+                        GrabModeAsync,
+                        GrabModeAsync,
+                        0,
+                        0
+                    );
+                }
             }
         }
     };
@@ -0,0 +1,359 @@
+use std::fs;
+use std::io::{ErrorKind, Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use serde_json::{json, Value};
+use x11::xlib;
+
+use crate::layout::Rect;
+use crate::socket::socket_path;
+use crate::state;
+use crate::wm::WindowExt;
+use crate::active_workspace;
+
+/* what a subscriber can ask for, see emit */
+pub const EVENTS: &[&str] = &["workspace", "window", "focus", "title", "layout", "floating", "fullscreen", "mode"];
+
+/* a subscriber with this much unread (or a client sending a line this long) is dropped rather than buffering without bound */
+pub const MAX_PENDING: usize = 1 << 20;
+
+/* bumped whenever a field of the query replies changes meaning or goes away, new fields don't bump it */
+pub const SCHEMA_VERSION: u32 = 1;
+
+/* the control socket, one json command per line, each answered by one json reply line          */
+/* e.g. {"command": "goto_workspace", "workspace": 1} -> {"success": true}                      */
+/* or   {"command": "spawn", "shell": "alacritty"}    -> {"success": false, "error": "..."}     */
+/* queries add their result to the reply, {"command": "get_active"} -> {"success": true,       */
+/* "version": 1, "active": {"workspace": 0, "window": 4194313, "focus_locked": false}}          */
+/* after {"command": "subscribe", "events": ["focus"]} the client also gets a line per event,  */
+/* {"event": "focus", ...}, until it disconnects                                               */
+pub struct Ipc {
+    pub listener: UnixListener,
+    pub path: PathBuf,
+    pub clients: Vec<IpcClient>,
+    pub focused: xlib::Window /* the last focus reported to subscribers */
+}
+
+pub struct IpcClient {
+    pub stream: UnixStream,
+    pub input: Vec<u8> /* bytes of a line not complete yet */,
+    pub output: Vec<u8> /* replies the client hasn't read yet */,
+    pub eof: bool /* done sending, dropped once its replies are out */,
+    pub events: Vec<String> /* subscribed to, empty if not a subscriber */,
+    pub closed: bool
+}
+
+pub enum IpcCommand {
+    GotoWorkspace(usize),
+    SendActiveWindowToWorkspace(usize),
+    FocusNext,
+    CloseActive,
+    SeparatorModify(i32),
+    ToggleFloating,
+    ToggleFullscreen,
+    Spawn(String),
+    GetState,
+    GetWorkspaces,
+    GetActive,
+    GetMonitors,
+    Subscribe(Vec<String>)
+}
+
+fn field<'a>(json: &'a Value, key: &str) -> Result<&'a Value, String> {
+    json.get(key).ok_or_else(|| format!("missing field \"{}\"", key))
+}
+
+fn int_field(json: &Value, key: &str) -> Result<i64, String> {
+    field(json, key)?.as_i64().ok_or_else(|| format!("field \"{}\" must be an integer", key))
+}
+
+pub fn parse_command(line: &str) -> Result<IpcCommand, String> {
+    let json: Value = serde_json::from_str(line).map_err(|err| format!("invalid json: {}", err))?;
+    let name = field(&json, "command")?.as_str().ok_or("field \"command\" must be a string")?;
+    let workspace = || int_field(&json, "workspace").and_then(|no| usize::try_from(no).map_err(|_| "workspace must not be negative".to_string()));
+
+    Ok(match name {
+        "goto_workspace" => IpcCommand::GotoWorkspace(workspace()?),
+        "send_active_window_to_workspace" => IpcCommand::SendActiveWindowToWorkspace(workspace()?),
+        "focus_next" => IpcCommand::FocusNext,
+        "close_active" => IpcCommand::CloseActive,
+        "separator_modify" => IpcCommand::SeparatorModify(int_field(&json, "amount")? as i32),
+        "toggle_floating" => IpcCommand::ToggleFloating,
+        "toggle_fullscreen" => IpcCommand::ToggleFullscreen,
+        "spawn" => IpcCommand::Spawn(field(&json, "shell")?.as_str().ok_or("field \"shell\" must be a string")?.to_string()),
+        "get_state" => IpcCommand::GetState,
+        "get_workspaces" => IpcCommand::GetWorkspaces,
+        "get_active" => IpcCommand::GetActive,
+        "get_monitors" => IpcCommand::GetMonitors,
+        "subscribe" => {
+            let events = match json.get("events") {
+                None => EVENTS.iter().map(|event| event.to_string()).collect(),
+                Some(events) => events.as_array().ok_or("field \"events\" must be an array")?.iter()
+                    .map(|event| event.as_str().map(str::to_string).ok_or("field \"events\" must hold strings"))
+                    .collect::<Result<Vec<String>, &str>>()?
+            };
+            if let Some(unknown) = events.iter().find(|event| !EVENTS.contains(&event.as_str())) {
+                return Err(format!("unknown event \"{}\"", unknown));
+            }
+            IpcCommand::Subscribe(events)
+        },
+        _ => return Err(format!("unknown command \"{}\"", name))
+    })
+}
+
+impl state::State<'_> {
+    /* binds the socket, leaves ipc off if another instance already listens on it */
+    pub fn start_ipc(&mut self) {
+        let path = socket_path();
+        if path.exists() {
+            if UnixStream::connect(&path).is_ok() {
+                println!("xroagwem: warning - {} is in use, ipc disabled", path.display());
+                return;
+            }
+            /* left behind by a previous run */
+            let _ = fs::remove_file(&path);
+        }
+        let listener = match UnixListener::bind(&path) {
+            Ok(listener) => listener,
+            Err(err) => {
+                println!("xroagwem: warning - cannot bind {}: {}", path.display(), err);
+                return;
+            }
+        };
+        if listener.set_nonblocking(true).is_err() { return }
+        self.ipc = Some(Ipc { listener, path, clients: Vec::new(), focused: 0 });
+    }
+
+    /* what the event loop polls for on behalf of ipc, drops the clients that are done first */
+    pub fn ipc_fds(&mut self) -> Vec<libc::pollfd> {
+        let ipc = match &mut self.ipc {
+            Some(ipc) => ipc,
+            None => return Vec::new()
+        };
+        ipc.clients.retain(|client| !(client.closed || (client.eof && client.output.is_empty())));
+        let mut fds = vec![libc::pollfd { fd: ipc.listener.as_raw_fd(), events: libc::POLLIN, revents: 0 }];
+        for client in &ipc.clients {
+            let mut events = if client.eof { 0 } else { libc::POLLIN };
+            if !client.output.is_empty() { events |= libc::POLLOUT; }
+            fds.push(libc::pollfd { fd: client.stream.as_raw_fd(), events, revents: 0 });
+        }
+        fds
+    }
+
+    /* accepts new clients, runs every complete line they sent and writes out what replies fit */
+    pub fn ipc_poll(&mut self) {
+        let mut lines: Vec<(usize, String)> = Vec::new();
+        match &mut self.ipc {
+            Some(ipc) => {
+                while let Ok((stream, _)) = ipc.listener.accept() {
+                    if stream.set_nonblocking(true).is_ok() {
+                        ipc.clients.push(IpcClient { stream, input: Vec::new(), output: Vec::new(), eof: false, events: Vec::new(), closed: false });
+                    }
+                }
+                for (i, client) in ipc.clients.iter_mut().enumerate() {
+                    client.receive();
+                    while let Some(end) = client.input.iter().position(|b| *b == b'\n') {
+                        let line: Vec<u8> = client.input.drain(..=end).collect();
+                        let line = String::from_utf8_lossy(&line).trim().to_string();
+                        if !line.is_empty() { lines.push((i, line)); }
+                    }
+                    if client.input.len() > MAX_PENDING { client.closed = true; }
+                }
+            },
+            None => return
+        }
+
+        for (i, line) in lines {
+            let reply = match parse_command(&line).and_then(|command| match command {
+                IpcCommand::Subscribe(events) => {
+                    if let Some(ipc) = &mut self.ipc { ipc.clients[i].events = events; }
+                    Ok(Value::Null)
+                },
+                command => self.run_command(command)
+            }) {
+                Ok(mut result) => {
+                    let mut reply = json!({ "success": true });
+                    if let Some(fields) = result.as_object_mut() { reply.as_object_mut().unwrap().append(fields); }
+                    reply
+                },
+                Err(err) => json!({ "success": false, "error": err })
+            };
+            if let Some(ipc) = &mut self.ipc {
+                ipc.clients[i].output.extend_from_slice(format!("{}\n", reply).as_bytes());
+            }
+        }
+
+        if let Some(ipc) = &mut self.ipc {
+            for client in ipc.clients.iter_mut() { client.send(); }
+        }
+    }
+
+    /* Null for commands, the fields to add to the reply for queries */
+    pub fn run_command(&mut self, command: IpcCommand) -> Result<Value, String> {
+        match command {
+            IpcCommand::GotoWorkspace(no) | IpcCommand::SendActiveWindowToWorkspace(no) if no >= self.workspaces.len() =>
+                return Err(format!("no workspace {}, there are {}", no, self.workspaces.len())),
+            IpcCommand::SendActiveWindowToWorkspace(_) | IpcCommand::CloseActive | IpcCommand::ToggleFloating | IpcCommand::ToggleFullscreen
+                if self.no_active_window() =>
+                return Err("no active window".to_string()),
+            IpcCommand::GotoWorkspace(no) => self.goto_workspace(no),
+            IpcCommand::SendActiveWindowToWorkspace(no) => self.send_active_window_to_workspace(no),
+            IpcCommand::FocusNext => self.focus_next(),
+            IpcCommand::CloseActive => self.close_active(),
+            IpcCommand::SeparatorModify(amount) => self.separator_modify(amount),
+            IpcCommand::ToggleFloating => self.toggle_floating(),
+            IpcCommand::ToggleFullscreen => self.toggle_fullscreen(),
+            IpcCommand::Spawn(shell) => {
+                Command::new("sh").args(["-c", &shell]).stdin(Stdio::null()).spawn().map_err(|err| err.to_string())?;
+            },
+            IpcCommand::GetState => return Ok(self.dump_state()),
+            IpcCommand::GetWorkspaces => return Ok(json!({ "version": SCHEMA_VERSION, "workspaces": self.dump_workspaces() })),
+            IpcCommand::GetActive => return Ok(json!({ "version": SCHEMA_VERSION, "active": self.dump_active() })),
+            IpcCommand::GetMonitors => return Ok(json!({ "version": SCHEMA_VERSION, "monitors": self.dump_monitors() })),
+            IpcCommand::Subscribe(_) => return Err("subscribe only works over the socket".to_string())
+        }
+        Ok(Value::Null)
+    }
+
+    pub fn subscribed(&self, event: &str) -> bool {
+        self.ipc.as_ref().is_some_and(|ipc| ipc.clients.iter().any(|client| !client.closed && client.events.iter().any(|e| e == event)))
+    }
+
+    /* sends {"event": event, ...payload} to its subscribers, the payload is only built if there are any */
+    pub fn emit(&mut self, event: &str, payload: impl FnOnce(&mut Self) -> Value) {
+        if !self.subscribed(event) { return }
+        let mut message = json!({ "event": event });
+        if let Some(fields) = payload(self).as_object_mut() { message.as_object_mut().unwrap().append(fields); }
+        let line = format!("{}\n", message);
+
+        if let Some(ipc) = &mut self.ipc {
+            for client in ipc.clients.iter_mut().filter(|client| !client.closed && client.events.iter().any(|e| e == event)) {
+                client.output.extend_from_slice(line.as_bytes());
+                client.send();
+                if client.output.len() > MAX_PENDING {
+                    println!("xroagwem: warning - dropping an ipc subscriber that stopped reading");
+                    client.closed = true;
+                }
+            }
+        }
+    }
+
+    /* called on every retile, reports the active window if it changed since */
+    pub fn emit_focus(&mut self) {
+        let window = self.active.window;
+        match &mut self.ipc {
+            Some(ipc) if ipc.focused != window => ipc.focused = window,
+            _ => return
+        }
+        self.emit("focus", |state| {
+            let workspace = state.active.workspace;
+            let focused = state.workspaces[workspace].windows.contains(&window).then(|| state.dump_window(workspace, window));
+            json!({ "active": state.dump_active(), "window": focused })
+        });
+    }
+
+    pub fn emit_layout(&mut self) {
+        self.emit("layout", |state| {
+            let space = &active_workspace!(state);
+            let custom = space.custom.as_ref();
+            json!({
+                "workspace": state.active.workspace,
+                "windows": space.windows,
+                "separator": custom.map(|custom| custom.separator),
+                "inner_gap": custom.map(|custom| custom.inner_gap)
+            })
+        });
+    }
+
+    pub fn dump_state(&mut self) -> Value {
+        let bars: Vec<Value> = self.bars.iter().map(|bar| rect_json(bar.rect)).collect();
+        json!({
+            "version": SCHEMA_VERSION,
+            "workspaces": self.dump_workspaces(),
+            "active": self.dump_active(),
+            "monitors": self.dump_monitors(),
+            "bar": { "visible": self.bar_visible, "rect": bars.first(), "rects": bars }
+        })
+    }
+
+    pub fn dump_workspaces(&mut self) -> Value {
+        let mut workspaces = Vec::new();
+        for no in 0..self.workspaces.len() {
+            let windows: Vec<Value> = self.workspaces[no].windows.clone().into_iter().map(|window| self.dump_window(no, window)).collect();
+            let space = &self.workspaces[no];
+            workspaces.push(json!({
+                "index": no,
+                "tag": space.tag,
+                "active": no == self.active.workspace,
+                "separator": space.custom.as_ref().map(|custom| custom.separator),
+                "windows": windows
+            }));
+        }
+        Value::from(workspaces)
+    }
+
+    pub fn dump_window(&mut self, workspace: usize, window: xlib::Window) -> Value {
+        let (instance, class) = window.get_class(self);
+        let title = window.get_title(self);
+        let rect = window.get_rect(self);
+        let custom = self.workspaces[workspace].custom.as_ref();
+        json!({
+            "id": window,
+            "title": title,
+            "instance": instance,
+            "class": class,
+            "floating": custom.is_some_and(|custom| custom.floating_windows.contains(&window)),
+            "fullscreen": custom.is_some_and(|custom| custom.fullscreen_windows.contains(&window)),
+            "minimised": custom.is_some_and(|custom| custom.minimised.contains(&window)),
+            "rect": rect_json(rect)
+        })
+    }
+
+    pub fn dump_active(&self) -> Value {
+        let window = self.active.window;
+        json!({
+            "workspace": self.active.workspace,
+            "window": (window != 0 && window != self.root).then_some(window),
+            "focus_locked": self.active.focus_locked
+        })
+    }
+
+    pub fn dump_monitors(&mut self) -> Value {
+        Value::from(self.monitors().into_iter().map(rect_json).collect::<Vec<Value>>())
+    }
+}
+
+fn rect_json(rect: Rect) -> Value {
+    json!({ "x": rect.0, "y": rect.1, "width": rect.2, "height": rect.3 })
+}
+
+impl IpcClient {
+    fn receive(&mut self) {
+        if self.eof { return }
+        let mut buf = [0u8; 4096];
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => { self.eof = true; return }
+                Ok(n) => self.input.extend_from_slice(&buf[..n]),
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return,
+                Err(_) => { self.closed = true; return }
+            }
+        }
+    }
+
+    fn send(&mut self) {
+        while !self.output.is_empty() {
+            match self.stream.write(&self.output) {
+                Ok(0) => { self.closed = true; return }
+                Ok(n) => { self.output.drain(..n); }
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return,
+                Err(_) => { self.closed = true; return }
+            }
+        }
+    }
+}
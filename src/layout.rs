@@ -0,0 +1,241 @@
+use crate::style::Paddings;
+
+/* (x, y, width, height) - the same tuple do_map and get_rect work with */
+pub type Rect = (i32, i32, u32, u32);
+
+const MIN_SIZE: i64 = 1;
+
+/* the area with the given margins taken off each side */
+pub fn shrink(area: Rect, by: &Paddings) -> Rect {
+    let width = (area.2 as i64 - by.left as i64 - by.right as i64).max(MIN_SIZE);
+    let height = (area.3 as i64 - by.top as i64 - by.bottom as i64).max(MIN_SIZE);
+    (area.0 + by.left as i32, area.1 + by.top as i32, width as u32, height as u32)
+}
+
+/* window geometry for a cell - X keeps the border outside of the size */
+pub fn inset_border(cell: Rect, border: u32) -> Rect {
+    let width = (cell.2 as i64 - border as i64 * 2).max(MIN_SIZE);
+    let height = (cell.3 as i64 - border as i64 * 2).max(MIN_SIZE);
+    (cell.0, cell.1, width as u32, height as u32)
+}
+
+/* the cell with its edge facing `bar` moved `margin` past it, if the two share a column - a bar */
+/* nearer the cell's top pushes the top down, otherwise the bottom up                           */
+pub fn clear_of(cell: Rect, bar: Rect, margin: u32) -> Rect {
+    let (left, right) = (cell.0 as i64, cell.0 as i64 + cell.2 as i64);
+    if bar.0 as i64 >= right || bar.0 as i64 + bar.2 as i64 <= left { return cell }
+    let (mut top, mut bottom) = (cell.1 as i64, cell.1 as i64 + cell.3 as i64);
+    let (bar_top, bar_bottom) = (bar.1 as i64, bar.1 as i64 + bar.3 as i64);
+    if bar_top + bar_bottom <= top + bottom {
+        top = top.max(bar_bottom + margin as i64);
+    } else {
+        bottom = bottom.min(bar_top - margin as i64);
+    }
+    (cell.0, top as i32, cell.2, (bottom - top).max(MIN_SIZE) as u32)
+}
+
+/* splits the area into `n` rows separated by `gap`, the last row takes the rounding leftover */
+pub fn rows(area: Rect, n: usize, gap: u32) -> Vec<Rect> {
+    if n == 0 { return Vec::new() }
+    let gaps = gap as i64 * (n as i64 - 1);
+    let height = ((area.3 as i64 - gaps) / n as i64).max(MIN_SIZE);
+    let bottom = area.1 as i64 + area.3 as i64;
+
+    (0..n).map(|i| {
+        let y = area.1 as i64 + i as i64 * (height + gap as i64);
+        let h = if i == n - 1 { (bottom - y).max(MIN_SIZE) } else { height };
+        (area.0, y as i32, area.2, h as u32)
+    }).collect()
+}
+
+/* cells of the cascade layout in Space::windows order - the stack top to bottom, then the master */
+/* left of the separator, outer gaps are expected to be already taken off the area                */
+pub fn cascade(area: Rect, n: usize, separator: i32, inner_gap: u32) -> Vec<Rect> {
+    if n == 0 { return Vec::new() }
+    if n == 1 { return vec![area] }
+
+    let left = area.0 as i64;
+    let right = area.0 as i64 + area.2 as i64;
+    let gap = inner_gap as i64;
+    let master_right = (separator as i64 - gap / 2).clamp(left + MIN_SIZE, (right - gap - MIN_SIZE).max(left + MIN_SIZE));
+    let stack_left = master_right + gap;
+
+    let master = (area.0, area.1, (master_right - left) as u32, area.3);
+    let stack = (stack_left as i32, area.1, (right - stack_left).max(MIN_SIZE) as u32, area.3);
+
+    let mut cells = rows(stack, n - 1, inner_gap);
+    cells.push(master);
+    cells
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Slot {
+    Left,
+    Center,
+    Right
+}
+
+/* horizontal (x, width) of each bar widget, given its slot and desired width - None shares what's left */
+/* widgets in one slot keep their order and are `separator` apart, Some(0) ones take no separator      */
+pub fn bar(width: u32, widgets: &[(Slot, Option<u32>)], separator: u32) -> Vec<(i32, u32)> {
+    let shown = |w: &Option<u32>| *w != Some(0);
+    let slot_count = |slot: Slot| widgets.iter().filter(|(s, w)| *s == slot && shown(w)).count() as i64;
+    let separators: i64 = [Slot::Left, Slot::Center, Slot::Right].iter()
+        .map(|slot| (slot_count(*slot) - 1).max(0) * separator as i64)
+        .sum();
+    let fixed: i64 = widgets.iter().filter_map(|(_, w)| *w).map(|w| w as i64).sum();
+    let expanding = widgets.iter().filter(|(_, w)| w.is_none()).count() as i64;
+    let share = if expanding == 0 { 0 } else { ((width as i64 - fixed - separators) / expanding).max(0) };
+
+    let widths: Vec<i64> = widgets.iter().map(|(_, w)| w.map(|w| w as i64).unwrap_or(share)).collect();
+    let slot_width = |slot: Slot| -> i64 {
+        let items: Vec<i64> = widgets.iter().zip(&widths).filter(|((s, w), _)| *s == slot && shown(w)).map(|(_, w)| *w).collect();
+        items.iter().sum::<i64>() + (items.len() as i64 - 1).max(0) * separator as i64
+    };
+
+    let left_end = slot_width(Slot::Left);
+    let right_start = width as i64 - slot_width(Slot::Right);
+    let center_width = slot_width(Slot::Center);
+    let center_start = ((width as i64 - center_width) / 2).clamp(left_end.min(right_start - center_width), (right_start - center_width).max(left_end));
+
+    let mut cursors = [0, center_start, right_start];
+    widgets.iter().zip(&widths).map(|((slot, desired), w)| {
+        let cursor = &mut cursors[*slot as usize];
+        let x = *cursor;
+        if shown(desired) { *cursor += w + separator as i64; }
+        (x as i32, *w as u32)
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AREA: Rect = (0, 20, 1920, 1060);
+
+    fn right(r: Rect) -> i32 { r.0 + r.2 as i32 }
+    fn bottom(r: Rect) -> i32 { r.1 + r.3 as i32 }
+
+    #[test]
+    fn shrink_takes_each_side_separately() {
+        let area = shrink(AREA, &Paddings { top: 1, bottom: 2, left: 3, right: 4 });
+        assert_eq!(area, (3, 21, 1913, 1057));
+    }
+
+    #[test]
+    fn single_window_fills_the_area() {
+        assert_eq!(cascade(AREA, 1, 960, 10), vec![AREA]);
+    }
+
+    #[test]
+    fn inner_gap_between_master_and_stack() {
+        let cells = cascade(AREA, 3, 960, 10);
+        let master = cells[2];
+        for stacked in &cells[..2] {
+            assert_eq!(stacked.0 - right(master), 10);
+        }
+    }
+
+    #[test]
+    fn inner_gap_between_stacked_windows() {
+        let cells = cascade(AREA, 5, 960, 7);
+        for pair in cells[..4].windows(2) {
+            assert_eq!(pair[1].1 - bottom(pair[0]), 7);
+        }
+    }
+
+    #[test]
+    fn cells_reach_the_area_edges() {
+        let cells = cascade(AREA, 4, 1000, 9);
+        let master = cells[3];
+        assert_eq!((master.0, master.1, bottom(master)), (AREA.0, AREA.1, bottom(AREA)));
+        assert_eq!(cells[0].1, AREA.1);
+        assert_eq!(bottom(cells[2]), bottom(AREA));
+        for stacked in &cells[..3] {
+            assert_eq!(right(*stacked), right(AREA));
+        }
+    }
+
+    #[test]
+    fn gaps_match_with_outer_gaps_applied() {
+        let outer = Paddings { top: 5, bottom: 5, left: 5, right: 5 };
+        let area = shrink(AREA, &outer);
+        let cells = cascade(area, 2, 960, 5);
+        assert_eq!(cells[1].0 - AREA.0, 5);
+        assert_eq!(cells[0].0 - right(cells[1]), 5);
+        assert_eq!(right(AREA) - right(cells[0]), 5);
+    }
+
+    #[test]
+    fn separator_is_kept_inside_the_area() {
+        let cells = cascade(AREA, 2, 5000, 10);
+        assert!(right(cells[1]) < right(AREA));
+        assert!(cells[0].2 >= 1);
+        let cells = cascade(AREA, 2, -5000, 10);
+        assert!(cells[1].2 >= 1);
+    }
+
+    #[test]
+    fn tiny_areas_do_not_underflow() {
+        let cells = cascade((0, 0, 3, 3), 6, 1, 10);
+        assert_eq!(cells.len(), 6);
+        assert!(cells.iter().all(|c| c.2 >= 1 && c.3 >= 1));
+        assert_eq!(inset_border((0, 0, 3, 3), 5), (0, 0, 1, 1));
+    }
+
+    #[test]
+    fn bar_slots_keep_to_their_edges() {
+        let widgets = [(Slot::Left, Some(80)), (Slot::Center, None), (Slot::Right, Some(200)), (Slot::Right, Some(100))];
+        let rects = bar(1000, &widgets, 10);
+        assert_eq!(rects[0], (0, 80));
+        assert_eq!(rects[2], (690, 200));
+        assert_eq!(rects[3], (900, 100));
+        /* the expanding one takes everything between the fixed ones */
+        assert_eq!(rects[1], (80, 610));
+    }
+
+    #[test]
+    fn bar_center_is_centered_when_there_is_room() {
+        let rects = bar(1000, &[(Slot::Left, Some(100)), (Slot::Center, Some(200))], 0);
+        assert_eq!(rects[1], (400, 200));
+    }
+
+    #[test]
+    fn bar_center_is_pushed_away_from_a_wide_side() {
+        let rects = bar(1000, &[(Slot::Left, Some(500)), (Slot::Center, Some(200))], 0);
+        assert_eq!(rects[1], (500, 200));
+    }
+
+    #[test]
+    fn bar_expanding_widgets_never_go_negative() {
+        let rects = bar(100, &[(Slot::Left, Some(300)), (Slot::Center, None)], 5);
+        assert_eq!(rects[1].1, 0);
+    }
+
+    #[test]
+    fn bar_hidden_widgets_take_no_separator() {
+        let rects = bar(1000, &[(Slot::Right, Some(100)), (Slot::Right, Some(0)), (Slot::Right, Some(100))], 10);
+        assert_eq!(rects[0], (790, 100));
+        assert_eq!(rects[2], (900, 100));
+    }
+
+    #[test]
+    fn cells_under_a_top_bar_start_below_it() {
+        let cell = clear_of((0, 10, 900, 500), (0, 0, 1920, 20), 10);
+        assert_eq!((cell.1, bottom(cell)), (30, 510));
+    }
+
+    #[test]
+    fn cells_beside_a_bar_keep_their_place() {
+        /* a bar on the other monitor, or well above the cell */
+        assert_eq!(clear_of((1920, 10, 900, 500), (0, 0, 1920, 20), 10), (1920, 10, 900, 500));
+        assert_eq!(clear_of((0, 400, 900, 500), (0, 0, 1920, 20), 10), (0, 400, 900, 500));
+    }
+
+    #[test]
+    fn cells_over_a_bottom_bar_end_above_it() {
+        /* a shorter monitor than the display, its bar is above the area's bottom */
+        let cell = clear_of((0, 10, 900, 1060), (0, 700, 1280, 20), 10);
+        assert_eq!((cell.1, bottom(cell)), (10, 690));
+    }
+}
@@ -0,0 +1,142 @@
+use std::mem;
+
+use x11::keysym;
+use x11::xft;
+use x11::xlib::{self, Window};
+
+use crate::config::STYLE;
+use crate::state::{self, WIDGETS};
+use crate::widgets::{draw_text, ellipsize, text_baseline, text_width_px, Ctx};
+use crate::wm::WindowExt;
+use crate::{active_workspace, active_workspace_wins};
+
+/* restore menu drawn over the bar while it's open */
+pub struct Picker {
+    pub windows: Vec<Window>,
+    pub selected: usize
+}
+
+const PICKER_ENTRY_MAX_PX: i32 = 300;
+
+impl state::State<'_> {
+    pub fn minimise_active(&mut self) {
+        let window = self.active.window;
+        if !active_workspace_wins!(self).contains(&window) || self.is_minimised(window) { return }
+        if let Some(custom) = &mut active_workspace!(self).custom {
+            custom.minimised.push(window);
+        } else {
+            return;
+        }
+        window.do_unmap(self);
+        self.focus_next();
+        if self.active.window == window { self.active.window = self.root; }
+        self.retile();
+    }
+
+    pub fn restore_last(&mut self) {
+        let last = active_workspace!(self).custom.as_ref().and_then(|c| c.minimised.last().copied());
+        if let Some(window) = last { self.restore(window); }
+    }
+
+    pub fn restore(&mut self, window: Window) {
+        if let Some(custom) = &mut active_workspace!(self).custom {
+            custom.minimised.retain(|w| *w != window);
+        }
+        let mut wa : xlib::XWindowAttributes = unsafe { mem::zeroed() };
+        if unsafe { xlib::XGetWindowAttributes(self.dpy, window, &mut wa) } == 0 {
+            /* died while minimised */
+            active_workspace_wins!(self).retain(|w| *w != window);
+            self.retile();
+            return;
+        }
+        self.focus(window);
+        self.retile();
+    }
+
+    pub fn open_restore_picker(&mut self) {
+        let windows = match &active_workspace!(self).custom {
+            Some(custom) if !custom.minimised.is_empty() => custom.minimised.iter().rev().copied().collect(),
+            _ => return
+        };
+        let grabbed = unsafe { xlib::XGrabKeyboard(self.dpy, self.root, xlib::True, xlib::GrabModeAsync, xlib::GrabModeAsync, xlib::CurrentTime) };
+        if grabbed != xlib::GrabSuccess {
+            println!("xroagwem: warning - cannot grab the keyboard for the restore picker");
+            return;
+        }
+        self.picker = Some(Picker { windows, selected: 0 });
+        if STYLE.bar.auto_hide && !self.bar_revealed {
+            self.bar_revealed = true;
+            self.place_bar();
+        }
+        self.draw_picker();
+    }
+
+    pub fn close_restore_picker(&mut self) {
+        if self.picker.take().is_none() { return }
+        /* a mode the picker was opened from still wants the keyboard */
+        if self.mode.is_none() { unsafe { xlib::XUngrabKeyboard(self.dpy, xlib::CurrentTime) }; }
+        self.draw_widgets(Ctx::Expose);
+    }
+
+    pub fn picker_key(&mut self, keysym: u32) {
+        let picker = match &mut self.picker {
+            Some(picker) => picker,
+            None => return
+        };
+        let len = picker.windows.len();
+        match keysym {
+            keysym::XK_Right | keysym::XK_l | keysym::XK_Tab => picker.selected = (picker.selected + 1) % len,
+            keysym::XK_Left | keysym::XK_h => picker.selected = (picker.selected + len - 1) % len,
+            keysym::XK_Return => {
+                let window = picker.windows[picker.selected];
+                self.close_restore_picker();
+                self.restore(window);
+                return;
+            },
+            keysym::XK_Escape => {
+                self.close_restore_picker();
+                return;
+            },
+            _ => {}
+        }
+        self.draw_picker();
+    }
+
+    pub fn draw_picker(&mut self) {
+        let font = match unsafe { WIDGETS.first() } {
+            Some(widget) => widget.font,
+            None => return
+        };
+        let (windows, selected) = match &self.picker {
+            Some(picker) => (picker.windows.clone(), picker.selected),
+            None => return
+        };
+        /* over the first bar, the others keep the widgets */
+        self.bar = 0;
+        self.xft_draw = self.bars[0].xft_draw;
+        let bar_width = self.bars[0].rect.2;
+        let height = self.bar_height;
+        let pad = text_width_px(self, font, "A");
+        let baseline = text_baseline(font, height);
+
+        let labels: Vec<String> = windows.iter().map(|w| {
+            let title = w.get_title(self);
+            ellipsize(self, font, &title, PICKER_ENTRY_MAX_PX)
+        }).collect();
+        let widths: Vec<i32> = labels.iter().map(|l| text_width_px(self, font, l)).collect();
+
+        unsafe { xft::XftDrawRect(self.xft_draw, &self.colors.normal.bg, 0, 0, bar_width, height) };
+        let mut offset = 0;
+        for (i, label) in labels.iter().enumerate() {
+            let (bgcol, fgcol) = if i == selected {
+                (self.colors.normal.fg, self.colors.normal.bg)
+            } else {
+                (self.colors.normal.bg, self.colors.dimmed.fg)
+            };
+            unsafe { xft::XftDrawRect(self.xft_draw, &bgcol, offset, 0, (widths[i] + pad * 2) as u32, height) };
+            draw_text(self, self.xft_draw, fgcol, font, offset + pad, baseline, label);
+            offset += widths[i] + pad * 2;
+        }
+        self.present_bar(0, (0, 0, bar_width, height));
+    }
+}
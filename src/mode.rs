@@ -0,0 +1,91 @@
+use serde_json::json;
+use x11::{keysym, xlib};
+
+use crate::layout::Rect;
+use crate::state::{self, KEYBINDINGS};
+use crate::stats::{block_width, draw_block, Block};
+use crate::widgets::{Ctx, Widget, WidgetSpec};
+
+/* while a mode is on the keyboard is grabbed and only the bindings of that mode apply, Escape leaves it */
+pub struct Mode {
+    pub name: &'static str,
+    pub oneshot: bool /* a chord, left after the next key whether it's bound or not */
+}
+
+/* the name of the active mode, hidden outside of modes */
+#[derive(Default)]
+pub struct ModeIndicator;
+
+impl state::State<'_> {
+    pub fn enter_mode(&mut self, name: &'static str) {
+        self.set_mode(Some(Mode { name, oneshot: false }));
+    }
+
+    /* e.g. Mod+w then 1 - Mod+w enters the chord, the chord's bindings hold the second keys */
+    pub fn enter_chord(&mut self, name: &'static str) {
+        self.set_mode(Some(Mode { name, oneshot: true }));
+    }
+
+    pub fn exit_mode(&mut self) {
+        self.set_mode(None);
+    }
+
+    fn set_mode(&mut self, mode: Option<Mode>) {
+        /* the picker holds the keyboard itself */
+        if self.picker.is_some() { return }
+        match (&self.mode, &mode) {
+            (None, Some(_)) => {
+                let grabbed = unsafe { xlib::XGrabKeyboard(self.dpy, self.root, xlib::True, xlib::GrabModeAsync, xlib::GrabModeAsync, xlib::CurrentTime) };
+                if grabbed != xlib::GrabSuccess {
+                    println!("xroagwem: warning - cannot grab the keyboard for a mode");
+                    return;
+                }
+            },
+            (Some(_), None) => unsafe { xlib::XUngrabKeyboard(self.dpy, xlib::CurrentTime); },
+            _ => {}
+        }
+        self.mode = mode;
+        let name = self.mode.as_ref().map(|mode| mode.name);
+        self.emit("mode", |_| json!({ "mode": name }));
+        self.draw_widgets(Ctx::Retile);
+    }
+
+    /* a key pressed while a mode is on */
+    pub fn mode_key(&mut self, keysym: u32, mdky: u32) {
+        let (name, oneshot) = match &self.mode {
+            Some(mode) => (mode.name, mode.oneshot),
+            None => return
+        };
+        /* the modifiers of the next binding, or the release of the one that entered the mode */
+        if (keysym::XK_Shift_L..=keysym::XK_Hyper_R).contains(&keysym) || keysym == keysym::XK_ISO_Level3_Shift { return }
+        if keysym == keysym::XK_Escape {
+            self.exit_mode();
+            return;
+        }
+
+        let binding = unsafe { KEYBINDINGS.iter() }.find(|x| x.mode == Some(name) && x.key == keysym && x.mdky == mdky);
+        if oneshot { self.exit_mode(); }
+        if let Some(binding) = binding {
+            (binding.callback)(self);
+        }
+    }
+}
+
+impl WidgetSpec for ModeIndicator {
+    fn width(&self, state: &mut state::State, widget: &Widget) -> Option<u32> {
+        let block = mode_block(state);
+        Some(block_width(state, widget, &block))
+    }
+
+    fn draw(&self, state: &mut state::State, widget: &Widget, rect: Rect, _: Ctx) {
+        let block = mode_block(state);
+        draw_block(state, widget, rect, &block);
+    }
+}
+
+fn mode_block(state: &state::State) -> Block {
+    match &state.mode {
+        Some(mode) => Block { text: mode.name.to_string(), urgent: true, ..Block::default() },
+        None => Block::default()
+    }
+}
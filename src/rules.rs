@@ -0,0 +1,174 @@
+use std::sync::OnceLock;
+
+use regex::Regex;
+use x11::xlib::{self, Window};
+
+use crate::config::RULES;
+use crate::layout::Rect;
+use crate::state;
+use crate::wm::WindowExt;
+
+/* what a window is matched on - unset fields match anything */
+/* and what happens to it - unset fields keep the defaults    */
+#[derive(Clone, Copy)]
+pub struct Rule {
+    pub instance: Option<&'static str>,
+    pub class: Option<&'static str>,
+    pub title: Option<&'static str> /* regex */,
+    pub role: Option<&'static str>,
+    pub window_type: Option<&'static str> /* e.g. "dialog" for _NET_WM_WINDOW_TYPE_DIALOG */,
+
+    pub workspace: Option<usize>,
+    pub floating: Option<bool>,
+    pub fullscreen: Option<bool>,
+    pub geometry: Option<Geometry>,
+    pub monitor: Option<usize>,
+    pub border: Option<u32>,
+    pub focus: Option<bool> /* focus on map */,
+    pub slot: Option<usize> /* position in the tiling order, 0 is the master */,
+    pub titlebar: Option<bool>
+}
+
+#[derive(Clone, Copy)]
+pub enum Geometry {
+    Fixed(i32, i32, u32, u32) /* relative to the monitor */,
+    Centered(u32, u32)
+}
+
+impl Rule {
+    pub const ANY: Rule = Rule {
+        instance: None,
+        class: None,
+        title: None,
+        role: None,
+        window_type: None,
+        workspace: None,
+        floating: None,
+        fullscreen: None,
+        geometry: None,
+        monitor: None,
+        border: None,
+        focus: None,
+        slot: None,
+        titlebar: None
+    };
+
+    /* `title` is this rule's compiled title pattern, see title_patterns */
+    fn matches(&self, props: &WindowProps, title: &Option<Result<Regex, regex::Error>>) -> bool {
+        let eq = |pattern: Option<&str>, value: &str| pattern.is_none_or(|p| p.eq_ignore_ascii_case(value));
+        let title_matches = match title {
+            None => true,
+            Some(Ok(re)) => re.is_match(&props.title),
+            /* reported by check_rules */
+            Some(Err(_)) => false
+        };
+
+        eq(self.instance, &props.instance)
+            && eq(self.class, &props.class)
+            && eq(self.role, &props.role)
+            && self.window_type.is_none_or(|ty| props.window_type.as_deref().is_some_and(|t| t.eq_ignore_ascii_case(ty)))
+            && title_matches
+    }
+
+    /* fields set in `other` take precedence */
+    fn merge(self, other: &Rule) -> Rule {
+        Rule {
+            workspace: other.workspace.or(self.workspace),
+            floating: other.floating.or(self.floating),
+            fullscreen: other.fullscreen.or(self.fullscreen),
+            geometry: other.geometry.or(self.geometry),
+            monitor: other.monitor.or(self.monitor),
+            border: other.border.or(self.border),
+            focus: other.focus.or(self.focus),
+            slot: other.slot.or(self.slot),
+            titlebar: other.titlebar.or(self.titlebar),
+            ..self
+        }
+    }
+}
+
+pub struct WindowProps {
+    pub instance: String,
+    pub class: String,
+    pub title: String,
+    pub role: String,
+    pub window_type: Option<String>
+}
+
+impl WindowProps {
+    pub fn of(state: &mut state::State, window: Window) -> WindowProps {
+        let (instance, class) = window.get_class(state);
+        WindowProps {
+            instance,
+            class,
+            title: window.get_title(state),
+            role: window.get_role(state),
+            window_type: window.get_window_type(state)
+        }
+    }
+}
+
+/* the title regexes of RULES, compiled on first use, None for rules without one */
+fn title_patterns() -> &'static [Option<Result<Regex, regex::Error>>] {
+    static PATTERNS: OnceLock<Vec<Option<Result<Regex, regex::Error>>>> = OnceLock::new();
+    PATTERNS.get_or_init(|| RULES.iter().map(|rule| rule.title.map(Regex::new)).collect())
+}
+
+/* compiles the patterns at startup, warning once about each invalid one - such rules never match */
+pub fn check_rules() {
+    for (rule, pattern) in RULES.iter().zip(title_patterns()) {
+        if let (Some(title), Some(Err(err))) = (rule.title, pattern) {
+            println!("xroagwem: warning - invalid title regex in rule: {}: {}", title, err);
+        }
+    }
+}
+
+/* all the matching rules folded together, later ones win */
+pub fn resolve(props: &WindowProps) -> Rule {
+    RULES.iter()
+        .zip(title_patterns())
+        .filter(|(rule, title)| rule.matches(props, title))
+        .fold(Rule::ANY, |acc, (rule, _)| acc.merge(rule))
+}
+
+impl state::State<'_> {
+    /* puts a new window into the workspace the rule points to, returns that workspace */
+    pub fn apply_rule(&mut self, window: Window, rule: &Rule) -> usize {
+        let workspace = rule.workspace.filter(|w| *w < self.workspaces.len()).unwrap_or(self.active.workspace);
+        let space = &mut self.workspaces[workspace];
+
+        if let Some(custom) = &mut space.custom {
+            if rule.floating == Some(true) { custom.floating_windows.insert(window); }
+            if rule.fullscreen == Some(true) { custom.fullscreen_windows.insert(window); }
+            if let Some(border) = rule.border { custom.borders.insert(window, border); }
+        }
+
+        match rule.slot {
+            Some(0) | None => space.windows.push(window),
+            Some(slot) => {
+                let ix = (slot - 1).min(space.windows.len());
+                space.windows.insert(ix, window);
+            }
+        }
+
+        let monitors = self.monitors();
+        let monitor: Rect = monitors[rule.monitor.unwrap_or(0).min(monitors.len() - 1)];
+        let rect = match rule.geometry {
+            Some(Geometry::Fixed(x, y, w, h)) => Some((monitor.0 + x, monitor.1 + y, w, h)),
+            Some(Geometry::Centered(w, h)) => Some((
+                monitor.0 + (monitor.2 as i32 - w as i32) / 2,
+                monitor.1 + (monitor.3 as i32 - h as i32) / 2,
+                w, h
+            )),
+            /* fullscreen follows the monitor the window is on */
+            None if rule.monitor.is_some() => Some(monitor),
+            None => None
+        };
+        if let Some(rect) = rect {
+            let outer = window.outer(self);
+            unsafe { xlib::XMoveResizeWindow(self.dpy, outer, rect.0, rect.1, rect.2, rect.3) };
+        }
+
+        workspace
+    }
+}
@@ -0,0 +1,83 @@
+use std::mem;
+use std::process::Command;
+
+use x11::xlib::{self, Window};
+
+use crate::config::SCRATCHPADS;
+use crate::spawn_with_shell;
+use crate::state;
+use crate::wm::WindowExt;
+
+/* a floating window kept outside of the workspaces, shown over whichever one is active */
+pub struct Scratchpad {
+    pub name: &'static str,
+    pub command: &'static str,
+    pub args: &'static [&'static str],
+    pub class: &'static str /* WM_CLASS class the spawned window is recognised by */,
+    pub size: (u32, u32)
+}
+
+impl state::State<'_> {
+    pub fn toggle_scratchpad(&mut self, name: &str) {
+        let pad = match SCRATCHPADS.iter().find(|pad| pad.name == name) {
+            Some(pad) => pad,
+            None => return
+        };
+
+        let window = match self.scratchpads.get(pad.name).copied() {
+            Some(window) => window,
+            None => {
+                spawn_with_shell!(pad.command, args: pad.args);
+                return;
+            }
+        };
+
+        let mut wa : xlib::XWindowAttributes = unsafe { mem::zeroed() };
+        if unsafe { xlib::XGetWindowAttributes(self.dpy, window.outer(self), &mut wa) } == 0 {
+            /* the client is gone */
+            self.scratchpads.remove(pad.name);
+            spawn_with_shell!(pad.command, args: pad.args);
+            return;
+        }
+
+        if wa.map_state == xlib::IsViewable {
+            window.do_unmap(self);
+            if self.active.window == window { self.focus_next(); }
+        } else {
+            self.show_scratchpad(pad, window);
+        }
+    }
+
+    /* claims a newly mapped window if a scratchpad is waiting for it */
+    pub fn adopt_scratchpad(&mut self, window: Window) -> bool {
+        let (_, class) = window.get_class(self);
+        let pad = match SCRATCHPADS.iter().find(|pad| pad.class == class && !self.scratchpads.contains_key(pad.name)) {
+            Some(pad) => pad,
+            None => return false
+        };
+        self.scratchpads.insert(pad.name, window);
+        self.show_scratchpad(pad, window);
+        true
+    }
+
+    fn show_scratchpad(&mut self, pad: &Scratchpad, window: Window) {
+        let monitor = match self.active.window {
+            w if w == self.root => self.monitors()[0],
+            w => {
+                let rect = w.get_rect(self);
+                self.monitor_of(rect)
+            }
+        };
+        let (w, h) = (pad.size.0.min(monitor.2), pad.size.1.min(monitor.3));
+        let rect = (
+            monitor.0 + (monitor.2 - w) as i32 / 2,
+            monitor.1 + (monitor.3 - h) as i32 / 2,
+            w, h
+        );
+
+        self.active.window = window;
+        self.retile();
+        window.do_map(self, rect);
+        unsafe { xlib::XRaiseWindow(self.dpy, window.outer(self)) };
+    }
+}
@@ -0,0 +1,115 @@
+use std::cell::Cell;
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::Value;
+use x11::xlib;
+
+use crate::layout::Rect;
+use crate::stats::{block_width, draw_block, Block};
+use crate::widgets::{wake_bar, Ctx, Widget, WidgetSpec};
+use crate::{spawn_with_shell, state};
+
+/* shows what a shell command prints - every line is either plain text or a json object      */
+/* like {"text": "...", "color": "#ff0000", "urgent": true} ("full_text" works too, as in i3) */
+#[derive(Default)]
+pub struct Script {
+    pub command: &'static str,
+    pub interval: Option<Duration> /* rerun every interval showing the last line, None keeps one process running showing each line */,
+    pub on_click: Option<&'static str>,
+    pub output: Arc<Mutex<Block>> /* written from the command's thread */,
+    pub changed: Arc<AtomicBool>,
+    pub started: Cell<bool>
+}
+
+/* a line of output as a block, None for blank lines */
+pub fn parse_line(line: &str) -> Option<Block> {
+    let line = line.trim();
+    if line.is_empty() { return None }
+    if !line.starts_with('{') {
+        return Some(Block { text: line.to_string(), ..Block::default() });
+    }
+    let json: Value = match serde_json::from_str(line) {
+        Ok(json) => json,
+        Err(_) => return Some(Block { text: line.to_string(), ..Block::default() })
+    };
+    let text = json.get("text").or_else(|| json.get("full_text")).and_then(Value::as_str).unwrap_or_default();
+    Some(Block {
+        text: text.to_string(),
+        urgent: json.get("urgent").and_then(Value::as_bool).unwrap_or(false),
+        color: json.get("color").and_then(Value::as_str).map(str::to_string)
+    })
+}
+
+fn publish(output: &Mutex<Block>, changed: &AtomicBool, block: Block) {
+    let mut current = output.lock().unwrap();
+    if *current == block { return }
+    *current = block;
+    changed.store(true, Ordering::SeqCst);
+    wake_bar();
+}
+
+impl Script {
+    fn start(&self) {
+        let (command, interval) = (self.command, self.interval);
+        let (output, changed) = (self.output.clone(), self.changed.clone());
+
+        thread::spawn(move || {
+            let run = || Command::new("sh").args(["-c", command]).stdout(Stdio::piped()).stderr(Stdio::null()).spawn();
+            match interval {
+                Some(interval) => loop {
+                    if let Ok(child) = run() {
+                        if let Ok(result) = child.wait_with_output() {
+                            let stdout = String::from_utf8_lossy(&result.stdout).to_string();
+                            let last = stdout.lines().rev().find_map(parse_line);
+                            publish(&output, &changed, last.unwrap_or_default());
+                        }
+                    }
+                    thread::sleep(interval);
+                },
+                None => {
+                    let mut child = match run() {
+                        Ok(child) => child,
+                        Err(err) => {
+                            println!("xroagwem: warning - cannot run script widget {}: {}", command, err);
+                            return;
+                        }
+                    };
+                    let stdout = child.stdout.take().unwrap();
+                    for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                        if let Some(block) = parse_line(&line) { publish(&output, &changed, block); }
+                    }
+                    let _ = child.wait();
+                }
+            }
+        });
+    }
+}
+
+impl WidgetSpec for Script {
+    fn width(&self, state: &mut state::State, widget: &Widget) -> Option<u32> {
+        if !self.started.replace(true) { self.start(); }
+        let block = self.output.lock().unwrap().clone();
+        Some(block_width(state, widget, &block))
+    }
+
+    fn draw(&self, state: &mut state::State, widget: &Widget, rect: Rect, _: Ctx) {
+        let block = self.output.lock().unwrap().clone();
+        draw_block(state, widget, rect, &block);
+    }
+
+    fn updated(&self) -> bool {
+        self.changed.swap(false, Ordering::SeqCst)
+    }
+
+    fn on_click(&self, _: &mut state::State, _: &Widget, button: u32, _: (i32, i32)) {
+        if button != xlib::Button1 { return }
+        if let Some(command) = self.on_click {
+            spawn_with_shell!("sh", ["-c", command]);
+        }
+    }
+}
@@ -0,0 +1,55 @@
+/* shared by xroagwem and xroagwem-msg: where the control socket lives and how a command line becomes a request */
+/* each binary uses only part of it */
+#![allow(dead_code)]
+
+use std::env;
+use std::path::PathBuf;
+
+use serde_json::{json, Value};
+
+/* command name and the field its argument goes to, None for commands taking none */
+pub const COMMANDS: &[(&str, Option<&str>)] = &[
+    ("goto_workspace", Some("workspace")),
+    ("send_active_window_to_workspace", Some("workspace")),
+    ("focus_next", None),
+    ("close_active", None),
+    ("separator_modify", Some("amount")),
+    ("toggle_floating", None),
+    ("toggle_fullscreen", None),
+    ("spawn", Some("shell")),
+    ("get_state", None),
+    ("get_workspaces", None),
+    ("get_active", None),
+    ("get_monitors", None),
+    ("subscribe", Some("events"))
+];
+
+/* $XDG_RUNTIME_DIR/xroagwem-$DISPLAY.sock, /tmp if there is no runtime dir */
+pub fn socket_path() -> PathBuf {
+    let dir = env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from).unwrap_or_else(|| PathBuf::from("/tmp"));
+    let display = env::var("DISPLAY").unwrap_or_default();
+    dir.join(format!("xroagwem-{}.sock", display))
+}
+
+/* `goto_workspace 2` -> {"command": "goto_workspace", "workspace": 2}, the rest of the words make up a string argument */
+/* and `subscribe focus title` -> {"command": "subscribe", "events": ["focus", "title"]}, all events without any      */
+pub fn request(command: &str, args: &[String]) -> Result<Value, String> {
+    let field = match COMMANDS.iter().find(|(name, _)| *name == command) {
+        Some((_, field)) => *field,
+        None => return Err(format!("unknown command \"{}\"", command))
+    };
+    let mut request = json!({ "command": command });
+    match field {
+        None if !args.is_empty() => return Err(format!("{} takes no argument", command)),
+        None => {},
+        Some("events") if args.is_empty() => {},
+        Some("events") => request["events"] = Value::from(args.to_vec()),
+        Some(field) if args.is_empty() => return Err(format!("{} needs a {}", command, field)),
+        Some("shell") => request["shell"] = Value::from(args.join(" ")),
+        Some(field) => {
+            let number: i64 = args[0].parse().map_err(|_| format!("{} must be an integer, not \"{}\"", field, args[0]))?;
+            request[field] = Value::from(number);
+        }
+    }
+    Ok(request)
+}
@@ -1,8 +1,9 @@
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
-use x11::{xft::XftDraw, xlib::{self, Window}};
+use x11::{xft::{XftDraw, XftFont}, xlib::{self, Window}};
 
-use crate::{style::ColorSchemesXft, widgets, wm};
+use crate::{bar::BarWindow, decor::Decorations, ipc::Ipc, layout, minimise::Picker, mode::Mode, style::ColorSchemesXft, tray::Tray, widgets, wm};
 
 pub type Cursor = Cursor_<xlib::Cursor>;
 
@@ -15,13 +16,28 @@ pub struct Cursor_<T> {
 pub struct State<'a> {
     pub screen: i32,
     pub root: xlib::Window,
-    pub draw: xlib::Window,
-    pub xft_draw: *mut XftDraw,
+    pub bars: Vec<BarWindow> /* one per monitor with a bar, set up by place_bar */,
+    pub bar: usize /* the bar being drawn (or drawn last), see draw_bar */,
+    pub bar_visible: bool /* toggled by the user */,
+    pub bar_revealed: bool /* auto-hidden bars currently shown */,
+    pub bar_height: u32 /* from the widget fonts, see measure_bar */,
+    pub gc: xlib::GC,
+    pub xft_draw: *mut XftDraw /* the off-screen copy of `bar`, what the widgets draw on */,
+    pub fallback_fonts: Vec<*mut XftFont>,
     pub cursor: Cursor,
     pub dpy:&'a mut xlib::Display,
     pub workspaces: Vec<wm::Space<'a>>,
     pub colors : ColorSchemesXft,
     pub active: Active,
+    pub decor: Decorations,
+    pub scratchpads: HashMap<&'static str, Window> /* name -> window, never part of a Space */,
+    pub ignore_unmap: HashSet<Window> /* unmaps caused by the wm itself (e.g. reparenting) */,
+    pub picker: Option<Picker>,
+    pub tray: Option<Tray> /* None until the tray widget starts it, or if another tray runs */,
+    pub ipc: Option<Ipc> /* None if the socket couldn't be bound */,
+    pub mode: Option<Mode>,
+    pub numlock_mask: u32 /* see init::numlock_mask */,
+    pub detected_monitors: Vec<layout::Rect> /* see init::detect_monitors, config::MONITORS overrides it */,
 }
 
 pub struct Active {
@@ -51,6 +67,7 @@ macro_rules! mousemotion_type_decl {
 pub struct Keybinding {
     pub mdky: u32,
     pub key: u32,
+    pub mode: Option<&'static str> /* applies only inside that mode (see mode.rs), None for the grabbed top-level ones */,
     pub callback: Arc<dyn Fn(&mut State) + Send + Sync>
 }
 
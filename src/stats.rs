@@ -0,0 +1,512 @@
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::Local;
+use lazy_static::lazy_static;
+use sysinfo::{Components, Disks, NetworkData, Networks, System};
+use x11::xft::{self, XftColor};
+use x11::xlib;
+
+use crate::style::XftColorExt;
+use crate::layout::Rect;
+use crate::widgets::{draw_text, text_baseline, text_width_px, Ctx, Widget, WidgetSpec};
+use crate::{spawn_with_shell, state};
+
+lazy_static! {
+    static ref SYS: Mutex<System> = Mutex::new(System::new_all());
+}
+
+/* what a stat widget shows - drawn in the urgent colours once over its warning threshold, */
+/* empty text hides the widget, e.g. when the hardware isn't there                         */
+#[derive(Default, Clone, PartialEq)]
+pub struct Block {
+    pub text: String,
+    pub urgent: bool,
+    pub color: Option<String> /* text colour overriding the scheme, e.g. "#ff0000" */
+}
+
+pub struct Clock {
+    pub format: &'static str /* strftime */,
+    pub interval: Duration,
+    pub on_click: Option<&'static str>,
+    pub cached: RefCell<Option<Block>>
+}
+
+/* {usage}, per core also {core} */
+pub struct Cpu {
+    pub format: &'static str,
+    pub per_core: bool /* the format repeated for every core */,
+    pub warning: Option<f32> /* percent, any core over it when per core */,
+    pub interval: Duration,
+    pub on_click: Option<&'static str>,
+    pub cached: RefCell<Option<Block>>
+}
+
+/* {percent}, {used}, {free}, {total} - sizes in MiB */
+pub struct Memory {
+    pub format: &'static str,
+    pub warning: Option<f32> /* percent */,
+    pub interval: Duration,
+    pub on_click: Option<&'static str>,
+    pub cached: RefCell<Option<Block>>
+}
+
+/* the same as Memory */
+pub struct Swap {
+    pub format: &'static str,
+    pub warning: Option<f32>,
+    pub interval: Duration,
+    pub on_click: Option<&'static str>,
+    pub cached: RefCell<Option<Block>>
+}
+
+/* {1}, {5}, {15} */
+pub struct Load {
+    pub format: &'static str,
+    pub warning: Option<f64> /* on the one minute average */,
+    pub interval: Duration,
+    pub on_click: Option<&'static str>,
+    pub cached: RefCell<Option<Block>>
+}
+
+/* {days}, {hours}, {minutes} */
+pub struct Uptime {
+    pub format: &'static str,
+    pub interval: Duration,
+    pub on_click: Option<&'static str>,
+    pub cached: RefCell<Option<Block>>
+}
+
+/* {percent}, {status} as in sysfs e.g. "Discharging", {remaining} as h:mm until empty or full */
+pub struct Battery {
+    pub name: Option<&'static str> /* e.g. "BAT0", the first battery found if None */,
+    pub format: &'static str,
+    pub warning: Option<f32> /* percent, urgent at or below it while discharging */,
+    pub interval: Duration,
+    pub on_click: Option<&'static str>,
+    pub cached: RefCell<Option<Block>>
+}
+
+/* {interface}, {ip}, {rx}, {tx} - rates per second */
+pub struct Network {
+    pub interface: Option<&'static str> /* the first one that's up with an address if None */,
+    pub format: &'static str,
+    pub interval: Duration,
+    pub on_click: Option<&'static str>,
+    pub cached: RefCell<Option<Block>>,
+    pub networks: RefCell<(Networks, Instant)> /* with the time of the last refresh, for the rates */
+}
+
+/* {mount}, {percent}, {used}, {available}, {total} - sizes in GiB */
+pub struct Disk {
+    pub mount: &'static str,
+    pub format: &'static str,
+    pub warning: Option<f32> /* percent used */,
+    pub interval: Duration,
+    pub on_click: Option<&'static str>,
+    pub cached: RefCell<Option<Block>>
+}
+
+/* {label}, {temp} in degrees Celsius */
+pub struct Temperature {
+    pub label: Option<&'static str> /* part of the sensor label, the hottest sensor if None */,
+    pub format: &'static str,
+    pub warning: Option<f32> /* degrees, the sensor's critical temperature if None */,
+    pub interval: Duration,
+    pub on_click: Option<&'static str>,
+    pub cached: RefCell<Option<Block>>,
+    pub components: RefCell<Components>
+}
+
+impl Default for Clock {
+    fn default() -> Clock {
+        Clock { format: "%Y-%m-%d %H:%M:%S", interval: Duration::from_secs(1), on_click: None, cached: RefCell::default() }
+    }
+}
+
+impl Default for Cpu {
+    fn default() -> Cpu {
+        Cpu { format: "{usage} % CPU", per_core: false, warning: Some(90.0), interval: Duration::from_secs(2), on_click: None, cached: RefCell::default() }
+    }
+}
+
+impl Default for Memory {
+    fn default() -> Memory {
+        Memory { format: "{percent} % ({used} / {total} MiB)", warning: Some(90.0), interval: Duration::from_secs(5), on_click: None, cached: RefCell::default() }
+    }
+}
+
+impl Default for Swap {
+    fn default() -> Swap {
+        Swap { format: "{percent} % swap", warning: Some(50.0), interval: Duration::from_secs(5), on_click: None, cached: RefCell::default() }
+    }
+}
+
+impl Default for Load {
+    fn default() -> Load {
+        Load { format: "{1} {5} {15}", warning: None, interval: Duration::from_secs(5), on_click: None, cached: RefCell::default() }
+    }
+}
+
+impl Default for Uptime {
+    fn default() -> Uptime {
+        Uptime { format: "up {days}d {hours}h {minutes}m", interval: Duration::from_secs(60), on_click: None, cached: RefCell::default() }
+    }
+}
+
+impl Default for Battery {
+    fn default() -> Battery {
+        Battery { name: None, format: "{percent} % {remaining}", warning: Some(15.0), interval: Duration::from_secs(30), on_click: None, cached: RefCell::default() }
+    }
+}
+
+impl Default for Network {
+    fn default() -> Network {
+        Network {
+            interface: None, format: "{interface} {ip} {rx} / {tx}", interval: Duration::from_secs(2), on_click: None, cached: RefCell::default(),
+            networks: RefCell::new((Networks::new_with_refreshed_list(), Instant::now()))
+        }
+    }
+}
+
+impl Default for Disk {
+    fn default() -> Disk {
+        Disk { mount: "/", format: "{mount} {available} GiB free", warning: Some(90.0), interval: Duration::from_secs(30), on_click: None, cached: RefCell::default() }
+    }
+}
+
+impl Default for Temperature {
+    fn default() -> Temperature {
+        Temperature {
+            label: None, format: "{temp} °C", warning: None, interval: Duration::from_secs(5), on_click: None, cached: RefCell::default(),
+            components: RefCell::new(Components::new_with_refreshed_list())
+        }
+    }
+}
+
+/* replaces every {key} in the format with its value */
+pub fn fill(format: &str, values: &[(&str, String)]) -> String {
+    values.iter().fold(format.to_string(), |acc, (key, value)| acc.replace(&format!("{{{}}}", key), value))
+}
+
+fn over(value: f64, warning: Option<f64>) -> bool {
+    warning.is_some_and(|warning| value >= warning)
+}
+
+fn usage(format: &str, used: u64, total: u64, warning: Option<f32>) -> Block {
+    let percent = if total == 0 { 0.0 } else { used as f32 / total as f32 * 100.0 };
+    let text = fill(format, &[
+        ("percent", format!("{:02}", percent as u32)),
+        ("used", (used / 1024 / 1024).to_string()),
+        ("free", ((total - used) / 1024 / 1024).to_string()),
+        ("total", (total / 1024 / 1024).to_string())
+    ]);
+    Block { text, urgent: over(percent as f64, warning.map(|w| w as f64)), color: None }
+}
+
+const CLOCK_FALLBACK_FORMAT: &str = "%H:%M";
+static CLOCK_FORMAT_WARNED: AtomicBool = AtomicBool::new(false);
+
+impl Clock {
+    fn sample(&self) -> Block {
+        let now = Local::now();
+        let mut text = String::new();
+        if write!(text, "{}", now.format(self.format)).is_err() {
+            /* warned about once, the clock keeps ticking in the fallback format */
+            if !CLOCK_FORMAT_WARNED.swap(true, Ordering::Relaxed) {
+                println!("xroagwem: warning - invalid clock format: {}", self.format);
+            }
+            text = now.format(CLOCK_FALLBACK_FORMAT).to_string();
+        }
+        Block { text, urgent: false, color: None }
+    }
+}
+
+impl Cpu {
+    fn sample(&self) -> Block {
+        let mut sys = SYS.lock().unwrap();
+        sys.refresh_cpu_usage();
+        let warning = self.warning.map(|w| w as f64);
+
+        if !self.per_core {
+            let usage = sys.global_cpu_usage();
+            return Block {
+                text: fill(self.format, &[("usage", format!("{:02}", usage as u32))]),
+                urgent: over(usage as f64, warning),
+                color: None
+            };
+        }
+        let cores: Vec<String> = sys.cpus().iter().enumerate().map(|(i, cpu)| {
+            fill(self.format, &[("core", i.to_string()), ("usage", format!("{:02}", cpu.cpu_usage() as u32))])
+        }).collect();
+        Block {
+            text: cores.join(" "),
+            urgent: sys.cpus().iter().any(|cpu| over(cpu.cpu_usage() as f64, warning)),
+            color: None
+        }
+    }
+}
+
+impl Memory {
+    fn sample(&self) -> Block {
+        let mut sys = SYS.lock().unwrap();
+        sys.refresh_memory();
+        usage(self.format, sys.used_memory(), sys.total_memory(), self.warning)
+    }
+}
+
+impl Swap {
+    fn sample(&self) -> Block {
+        let mut sys = SYS.lock().unwrap();
+        sys.refresh_memory();
+        usage(self.format, sys.used_swap(), sys.total_swap(), self.warning)
+    }
+}
+
+impl Load {
+    fn sample(&self) -> Block {
+        let load = System::load_average();
+        Block {
+            text: fill(self.format, &[
+                ("1", format!("{:.2}", load.one)),
+                ("5", format!("{:.2}", load.five)),
+                ("15", format!("{:.2}", load.fifteen))
+            ]),
+            urgent: over(load.one, self.warning),
+            color: None
+        }
+    }
+}
+
+impl Uptime {
+    fn sample(&self) -> Block {
+        let uptime = System::uptime();
+        Block {
+            text: fill(self.format, &[
+                ("days", (uptime / 86400).to_string()),
+                ("hours", (uptime / 3600 % 24).to_string()),
+                ("minutes", (uptime / 60 % 60).to_string())
+            ]),
+            urgent: false,
+            color: None
+        }
+    }
+}
+
+impl Battery {
+    fn sample(&self) -> Block {
+        let dir = match find_battery(self.name) {
+            Some(dir) => dir,
+            None => return Block::default()
+        };
+        let read = |name: &str| fs::read_to_string(dir.join(name)).ok().map(|s| s.trim().to_string());
+        let number = |name: &str| read(name).and_then(|s| s.parse::<f64>().ok());
+
+        let percent = number("capacity").unwrap_or(0.0);
+        let status = read("status").unwrap_or_default();
+        let discharging = status == "Discharging";
+
+        /* energy in µWh with power in µW, or charge in µAh with current in µA, whichever the driver has */
+        let (now, full, rate) = match (number("energy_now"), number("energy_full"), number("power_now")) {
+            (Some(now), Some(full), Some(rate)) => (now, full, rate),
+            _ => (number("charge_now").unwrap_or(0.0), number("charge_full").unwrap_or(0.0), number("current_now").unwrap_or(0.0))
+        };
+        let hours = match (discharging, status == "Charging") {
+            _ if rate <= 0.0 => None,
+            (true, _) => Some(now / rate),
+            (_, true) => Some((full - now).max(0.0) / rate),
+            _ => None
+        };
+        let remaining = hours.map(|h| format!("{}:{:02}", h as u64, (h.fract() * 60.0) as u64)).unwrap_or_default();
+
+        Block {
+            text: fill(self.format, &[
+                ("percent", format!("{}", percent as u32)),
+                ("status", status),
+                ("remaining", remaining)
+            ]).trim().to_string(),
+            urgent: discharging && self.warning.is_some_and(|warning| percent <= warning as f64),
+            color: None
+        }
+    }
+}
+
+/* /sys/class/power_supply entry of the named battery or the first one there is */
+fn find_battery(name: Option<&str>) -> Option<PathBuf> {
+    let supplies = Path::new("/sys/class/power_supply");
+    if let Some(name) = name {
+        let dir = supplies.join(name);
+        return dir.exists().then_some(dir);
+    }
+    let mut dirs: Vec<PathBuf> = fs::read_dir(supplies).ok()?.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+    dirs.sort();
+    dirs.into_iter().find(|dir| fs::read_to_string(dir.join("type")).is_ok_and(|ty| ty.trim() == "Battery"))
+}
+
+impl Network {
+    fn sample(&self) -> Block {
+        let mut networks = self.networks.borrow_mut();
+        let (networks, last) = &mut *networks;
+        networks.refresh_list();
+        let elapsed = last.elapsed().as_secs_f64().max(0.001);
+        *last = Instant::now();
+
+        let address = |data: &NetworkData| data.ip_networks().iter()
+            .map(|net| net.addr)
+            .find(|addr| addr.is_ipv4() && !addr.is_loopback())
+            .or_else(|| data.ip_networks().iter().map(|net| net.addr).find(|addr| !addr.is_loopback()));
+        let is_up = |name: &str| fs::read_to_string(Path::new("/sys/class/net").join(name).join("operstate")).is_ok_and(|s| s.trim() == "up");
+
+        let found = match self.interface {
+            Some(name) => networks.get(name).map(|data| (name.to_string(), data)),
+            None => {
+                let mut names: Vec<&String> = networks.keys().filter(|name| is_up(name) && address(&networks[*name]).is_some()).collect();
+                names.sort();
+                names.first().map(|name| (name.to_string(), &networks[*name]))
+            }
+        };
+        let (name, data) = match found {
+            Some(found) => found,
+            None => return Block::default()
+        };
+
+        Block {
+            text: fill(self.format, &[
+                ("interface", name),
+                ("ip", address(data).map(|addr| addr.to_string()).unwrap_or_default()),
+                ("rx", rate(data.received() as f64 / elapsed)),
+                ("tx", rate(data.transmitted() as f64 / elapsed))
+            ]),
+            urgent: false,
+            color: None
+        }
+    }
+}
+
+fn rate(bytes_per_sec: f64) -> String {
+    match bytes_per_sec {
+        b if b >= 1024.0 * 1024.0 => format!("{:.1} MiB/s", b / 1024.0 / 1024.0),
+        b if b >= 1024.0 => format!("{:.0} KiB/s", b / 1024.0),
+        b => format!("{:.0} B/s", b)
+    }
+}
+
+impl Disk {
+    fn sample(&self) -> Block {
+        let disks = Disks::new_with_refreshed_list();
+        let disk = match disks.list().iter().find(|disk| disk.mount_point() == Path::new(self.mount)) {
+            Some(disk) => disk,
+            None => return Block::default()
+        };
+        let (total, available) = (disk.total_space(), disk.available_space());
+        let used = total.saturating_sub(available);
+        let percent = if total == 0 { 0.0 } else { used as f32 / total as f32 * 100.0 };
+        let gib = |bytes: u64| format!("{:.1}", bytes as f64 / 1024.0 / 1024.0 / 1024.0);
+
+        Block {
+            text: fill(self.format, &[
+                ("mount", self.mount.to_string()),
+                ("percent", format!("{:02}", percent as u32)),
+                ("used", gib(used)),
+                ("available", gib(available)),
+                ("total", gib(total))
+            ]),
+            urgent: over(percent as f64, self.warning.map(|w| w as f64)),
+            color: None
+        }
+    }
+}
+
+impl Temperature {
+    fn sample(&self) -> Block {
+        let mut components = self.components.borrow_mut();
+        components.refresh();
+        let sensor = components.list().iter()
+            .filter(|c| self.label.is_none_or(|label| c.label().contains(label)))
+            .filter(|c| !c.temperature().is_nan())
+            .max_by(|a, b| a.temperature().total_cmp(&b.temperature()));
+        let sensor = match sensor {
+            Some(sensor) => sensor,
+            None => return Block::default()
+        };
+
+        let warning = self.warning.or(sensor.critical()).map(|w| w as f64);
+        Block {
+            text: fill(self.format, &[
+                ("label", sensor.label().to_string()),
+                ("temp", format!("{:.0}", sensor.temperature()))
+            ]),
+            urgent: over(sensor.temperature() as f64, warning),
+            color: None
+        }
+    }
+}
+
+pub fn block_width(state: &mut state::State, widget: &Widget, block: &Block) -> u32 {
+    if block.text.is_empty() { return 0 }
+    let brk = text_width_px(state, widget.font, "A");
+    (text_width_px(state, widget.font, &block.text) + brk * 2) as u32
+}
+
+pub fn draw_block(state: &mut state::State, widget: &Widget, rect: Rect, block: &Block) {
+    if block.text.is_empty() { return }
+    let brk = text_width_px(state, widget.font, "A");
+    let height = state.bar_height;
+    let (bgcol, mut fgcol) = if block.urgent {
+        (state.colors.urgent.bg, state.colors.urgent.fg)
+    } else {
+        (state.colors.normal.fg, state.colors.normal.bg)
+    };
+    let custom = block.color.as_ref().map(|color| XftColor::from_str(state, color));
+    if let Some(color) = custom { fgcol = color; }
+    unsafe { xft::XftDrawRect(state.xft_draw, &bgcol, rect.0, 0, rect.2, height) };
+    draw_text(state, state.xft_draw, fgcol, widget.font, rect.0 + brk, text_baseline(widget.font, height), &block.text);
+    unsafe {
+        if let Some(mut color) = custom {
+            xft::XftColorFree(state.dpy, xlib::XDefaultVisual(state.dpy, state.screen), xlib::XDefaultColormap(state.dpy, state.screen), &mut color);
+        }
+    }
+}
+
+/* the widget side is the same for all of them, only sample differs */
+macro_rules! stat_widget {
+    ($($stat: ident),*) => {
+        $(
+            impl WidgetSpec for $stat {
+                fn width(&self, state: &mut state::State, widget: &Widget) -> Option<u32> {
+                    if self.cached.borrow().is_none() { self.refresh(); }
+                    let block = self.cached.borrow().clone().unwrap_or_default();
+                    Some(block_width(state, widget, &block))
+                }
+
+                fn draw(&self, state: &mut state::State, widget: &Widget, rect: Rect, _: Ctx) {
+                    let block = self.cached.borrow().clone().unwrap_or_default();
+                    draw_block(state, widget, rect, &block);
+                }
+
+                fn interval(&self) -> Option<Duration> {
+                    Some(self.interval)
+                }
+
+                fn refresh(&self) {
+                    *self.cached.borrow_mut() = Some(self.sample());
+                }
+
+                fn on_click(&self, _: &mut state::State, _: &Widget, button: u32, _: (i32, i32)) {
+                    if button != xlib::Button1 { return }
+                    if let Some(command) = self.on_click {
+                        spawn_with_shell!("sh", ["-c", command]);
+                    }
+                }
+            }
+        )*
+    };
+}
+
+stat_widget!(Clock, Cpu, Memory, Swap, Load, Uptime, Battery, Network, Disk, Temperature);
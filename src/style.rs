@@ -13,10 +13,45 @@ pub type ColorSchemeXft = ColorScheme_<XftColor>;
 pub struct Style {
     pub colors: ColorSchemes,
     pub border_thickness: u32,
-    pub useless_gap: u32,
-    pub paddings: Paddings
+    pub outer_gap: Paddings /* between the windows and the tiling area edges */,
+    pub inner_gap: u32 /* between neighbouring windows */,
+    pub smart_gaps: bool /* no gaps around a lone tiled window */,
+    pub smart_borders: bool /* no borders around a lone or fullscreen window */,
+    pub paddings: Paddings /* kept free around the tiling area, on top of the bar */,
+    pub titlebar: Titlebar,
+    pub bar: Bar
 }
 
+pub struct Bar {
+    pub padding: u32 /* above and below the text, the bar height follows from the widget fonts */,
+    pub fallback_fonts: &'static [&'static str] /* tried in order for glyphs missing in a widget's font */,
+    pub position: BarPosition,
+    pub monitors: BarMonitors,
+    pub auto_hide: bool /* shown only while the pointer is at the bar's screen edge */,
+    pub separator: &'static str /* drawn between widgets sharing a slot, empty for none */
+}
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum BarPosition {
+    Top,
+    Bottom
+}
+
+/* which monitors get a bar, each with all the widgets - the tray and the restore picker stay on the first */
+#[derive(PartialEq, Clone, Copy)]
+pub enum BarMonitors {
+    Display /* a single bar across the whole display */,
+    All,
+    Only(&'static [usize]) /* out of range ones are left out, the first monitor gets it if none is left */
+}
+
+pub struct Titlebar {
+    pub height: u32,
+    pub font: &'static str,
+    pub by_default: bool /* decorate every new window */
+}
+
+#[derive(Clone, Copy)]
 pub struct Paddings {
     pub top: u32,
     pub bottom: u32,
@@ -26,7 +61,9 @@ pub struct Paddings {
 
 pub struct ColorSchemes_<T> {
     pub normal: ColorScheme_<T>,
-    pub selected: ColorScheme_<T>
+    pub selected: ColorScheme_<T>,
+    pub dimmed: ColorScheme_<T> /* minimised windows */,
+    pub urgent: ColorScheme_<T> /* stat widgets over their warning threshold */
 }
 
 pub struct ColorScheme_<T> {
@@ -35,7 +72,7 @@ pub struct ColorScheme_<T> {
     pub border: T
 }
 
-trait XftColorExt {
+pub trait XftColorExt {
     fn from_str(state: &mut state::State, color: &str) ->XftColor;
 }
 
@@ -73,6 +110,8 @@ impl ColorSchemes_<&'static str> {
         ColorSchemes_ {
             normal: self.normal.to_xft(state),
             selected: self.selected.to_xft(state),
+            dimmed: self.dimmed.to_xft(state),
+            urgent: self.urgent.to_xft(state),
         }
     }
 }
@@ -0,0 +1,135 @@
+use std::cell::Cell;
+use std::mem;
+
+use x11::xlib::{self, Window};
+
+use crate::layout::Rect;
+use crate::state;
+use crate::widgets::{Ctx, Widget, WidgetSpec};
+
+const SYSTEM_TRAY_REQUEST_DOCK: i64 = 0;
+const XEMBED_EMBEDDED_NOTIFY: i64 = 0;
+const XEMBED_VERSION: i64 = 0;
+
+/* owner of the _NET_SYSTEM_TRAY_S<screen> selection, icons are embedded into the first bar's window */
+pub struct Tray {
+    pub window: Window /* owns the selection, never mapped */,
+    pub icons: Vec<Window>
+}
+
+/* the icons of the system tray, a square of the bar height each */
+#[derive(Default)]
+pub struct TrayIcons {
+    pub started: Cell<bool>
+}
+
+impl state::State<'_> {
+    /* takes the tray selection and announces it, leaves the tray off if another one runs */
+    pub fn start_tray(&mut self) {
+        let selection = self.atom(&format!("_NET_SYSTEM_TRAY_S{}", self.screen));
+        let orientation = self.atom("_NET_SYSTEM_TRAY_ORIENTATION");
+        let manager = self.atom("MANAGER");
+
+        unsafe {
+            if xlib::XGetSelectionOwner(self.dpy, selection) != 0 {
+                println!("xroagwem: warning - another system tray is running");
+                return;
+            }
+            let window = xlib::XCreateSimpleWindow(self.dpy, self.root, -1, -1, 1, 1, 0, 0, 0);
+            xlib::XSelectInput(self.dpy, window, xlib::StructureNotifyMask);
+            let horizontal: [u64; 1] = [0];
+            xlib::XChangeProperty(self.dpy, window, orientation, xlib::XA_CARDINAL, 32, xlib::PropModeReplace, horizontal.as_ptr() as *const u8, 1);
+            xlib::XSetSelectionOwner(self.dpy, selection, window, xlib::CurrentTime);
+            if xlib::XGetSelectionOwner(self.dpy, selection) != window {
+                xlib::XDestroyWindow(self.dpy, window);
+                return;
+            }
+
+            let mut ev: xlib::XClientMessageEvent = mem::zeroed();
+            ev.type_ = xlib::ClientMessage;
+            ev.window = self.root;
+            ev.message_type = manager;
+            ev.format = 32;
+            ev.data.set_long(0, xlib::CurrentTime as i64);
+            ev.data.set_long(1, selection as i64);
+            ev.data.set_long(2, window as i64);
+            xlib::XSendEvent(self.dpy, self.root, xlib::False, xlib::StructureNotifyMask, &mut ev as *mut _ as *mut xlib::XEvent);
+
+            self.tray = Some(Tray { window, icons: Vec::new() });
+        }
+    }
+
+    pub fn is_tray_icon(&self, window: Window) -> bool {
+        self.tray.as_ref().is_some_and(|tray| tray.icons.contains(&window))
+    }
+
+    /* a _NET_SYSTEM_TRAY_OPCODE message sent to the tray window */
+    pub fn tray_message(&mut self, ev: &xlib::XClientMessageEvent) {
+        if self.tray.as_ref().is_none_or(|tray| tray.window != ev.window) { return }
+        if ev.message_type != self.atom("_NET_SYSTEM_TRAY_OPCODE") || ev.data.get_long(1) != SYSTEM_TRAY_REQUEST_DOCK { return }
+        let icon = ev.data.get_long(2) as Window;
+        if icon == 0 || self.is_tray_icon(icon) { return }
+
+        let mut wa: xlib::XWindowAttributes = unsafe { mem::zeroed() };
+        if unsafe { xlib::XGetWindowAttributes(self.dpy, icon, &mut wa) } == 0 { return }
+        let size = self.bar_height;
+        let xembed = self.atom("_XEMBED");
+
+        unsafe {
+            xlib::XSelectInput(self.dpy, icon, xlib::StructureNotifyMask | xlib::PropertyChangeMask);
+            if wa.map_state != xlib::IsUnmapped { self.ignore_unmap.insert(icon); }
+            xlib::XAddToSaveSet(self.dpy, icon);
+            xlib::XReparentWindow(self.dpy, icon, self.bars[0].window, 0, 0);
+            xlib::XResizeWindow(self.dpy, icon, size, size);
+
+            let mut notify: xlib::XClientMessageEvent = mem::zeroed();
+            notify.type_ = xlib::ClientMessage;
+            notify.window = icon;
+            notify.message_type = xembed;
+            notify.format = 32;
+            notify.data.set_long(0, xlib::CurrentTime as i64);
+            notify.data.set_long(1, XEMBED_EMBEDDED_NOTIFY);
+            /* the embedder, the icon lives in the first bar rather than in the selection owner */
+            notify.data.set_long(3, self.bars[0].window as i64);
+            notify.data.set_long(4, XEMBED_VERSION);
+            xlib::XSendEvent(self.dpy, icon, xlib::False, xlib::NoEventMask, &mut notify as *mut _ as *mut xlib::XEvent);
+
+            xlib::XMapRaised(self.dpy, icon);
+        }
+        if let Some(tray) = &mut self.tray { tray.icons.push(icon); }
+        self.draw_widgets(Ctx::Retile);
+    }
+
+    /* drops an icon whose client went away */
+    pub fn undock(&mut self, window: Window) {
+        if !self.is_tray_icon(window) { return }
+        if let Some(tray) = &mut self.tray { tray.icons.retain(|icon| *icon != window); }
+        self.draw_widgets(Ctx::Retile);
+    }
+}
+
+impl WidgetSpec for TrayIcons {
+    fn width(&self, state: &mut state::State, _: &Widget) -> Option<u32> {
+        if !self.started.replace(true) { state.start_tray(); }
+        /* the icons can only be in one window, the other bars leave the tray out */
+        if state.bar != 0 { return Some(0) }
+        let icons = state.tray.as_ref().map(|tray| tray.icons.len()).unwrap_or(0);
+        Some(icons as u32 * state.bar_height)
+    }
+
+    fn draw(&self, state: &mut state::State, _: &Widget, rect: Rect, _: Ctx) {
+        let icons = match &state.tray {
+            Some(tray) if state.bar == 0 => tray.icons.clone(),
+            _ => return
+        };
+        let size = state.bar_height;
+        for (i, icon) in icons.iter().enumerate() {
+            let x = rect.0 + (i as u32 * size) as i32;
+            unsafe {
+                xlib::XMoveResizeWindow(state.dpy, *icon, x, 0, size, size);
+                /* icons draw on the parent-relative background, have them repaint on top of the new bar */
+                xlib::XClearArea(state.dpy, *icon, 0, 0, 0, 0, xlib::True);
+            }
+        }
+    }
+}
@@ -19,6 +19,20 @@ macro_rules! set_keybinding {
                 KEYBINDINGS.push(Keybinding {
                     mdky: $mdky,
                     key: $key, 
+                    mode: None,
+                    callback: Arc::new($cb)
+                });
+            }
+        }
+    };
+
+    (mode: $mode: expr, modkey: $mdky: expr, callback: $cb:expr, key: $key:expr) => {
+        {
+            unsafe {
+                KEYBINDINGS.push(Keybinding {
+                    mdky: $mdky,
+                    key: $key,
+                    mode: Some($mode),
                     callback: Arc::new($cb)
                 });
             }
@@ -30,16 +44,19 @@ macro_rules! set_keybinding {
 macro_rules! spawn_with_shell {
     ($command:expr, [ $($arg:expr),* ]) => {{
             Command::new($command)
-            .env("DISPLAY", ":1")
             $(    
                 .arg($arg)
             )*.spawn().expect("Failed to execute command")
     }};
 
+    ($command:expr, args: $args:expr) => {{
+            Command::new($command)
+            .args($args).spawn().expect("Failed to execute command")
+    }};
+
     ($command:expr) => {
         {
             Command::new($command)
-            .env("DISPLAY", ":1")
             .spawn().expect("Failed to execute command");
         }
     }
@@ -76,22 +93,26 @@ macro_rules! set_mousemotion {
 
 #[macro_export]
 macro_rules! add_widget {
-    ($state: expr, $widget: ident) => {
+    ($state: expr, $slot: expr, $widget: ident) => {
+        add_widget!($state, $slot, $widget, "")
+    };
+    ($state:expr, $slot: expr, $widget: ident { $($field:ident: $value:expr),* }, $font: expr) => {
         {
             unsafe {
                 WIDGETS.push(
                     Box::new(
                         Widget::new(
                             $state, 
-                            "",
-                            Box::new($widget {})
+                            $font,
+                            $slot,
+                            Box::new($widget { $($field: $value,)* ..Default::default() })
                         )
                     )
                 )
             }
         }
     };
-    ($state:expr, $widget: ident, $font: expr) => {
+    ($state:expr, $slot: expr, $widget: ident, $font: expr) => {
         {
             unsafe {
                 WIDGETS.push(
@@ -99,7 +120,8 @@ macro_rules! add_widget {
                         Widget::new(
                             $state, 
                             $font,
-                            Box::new($widget {})
+                            $slot,
+                            Box::new($widget::default())
                         )
                     )
                 )
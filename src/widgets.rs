@@ -1,176 +1,398 @@
+use std::cell::{Cell, RefCell};
 use std::ffi::CString;
 use std::mem;
 use std::ptr;
-use chrono::Local;
-
-use sysinfo::System;
+use std::time::{Duration, Instant};
+use lazy_static::lazy_static;
 
-use x11::xlib::ExposureMask;
 use x11::xlib::{self, XSetWindowAttributes};
 use x11::{xft, xrender};
-use std::sync::Mutex;
-use lazy_static::lazy_static;
 
-use crate::state;
+use crate::{layout, state};
 use crate::config::STYLE;
+use crate::layout::{Rect, Slot};
+use crate::state::WIDGETS;
+use crate::wm::WindowExt;
 
 #[derive(PartialEq, Clone, Copy)]
 pub enum Ctx {
     Retile, 
-    Expose   
+    Expose,
+    Tick /* some widgets' refresh interval has passed */
 }
 
 
 pub struct Widget {
     pub font: *mut xft::XftFont,
+    pub slot: Slot,
+    pub rect: Cell<Rect> /* where it was drawn last, relative to the bar */,
+    pub due: Cell<Option<Instant>> /* next refresh, None for widgets without an interval */,
+    pub stale: Cell<bool> /* refreshed but not drawn yet */,
     pub wspec: Box<dyn WidgetSpec>
 }
 
 impl Widget {
     pub fn draw(&self, state: &mut state::State, context:Ctx){
-        self.wspec.draw(state, self, context);
+        self.wspec.draw(state, self, self.rect.get(), context);
     }
 
-    pub fn new(state: &mut state::State, font: &str, wspec: Box<dyn WidgetSpec>) -> Widget {
+    pub fn new(state: &mut state::State, font: &str, slot: Slot, wspec: Box<dyn WidgetSpec>) -> Widget {
         Widget {
-            font: unsafe { xft::XftFontOpenName(state.dpy, state.screen, font.as_ptr() as *const i8) },
-            wspec: wspec
+            font: open_font(state, font),
+            slot,
+            rect: Cell::new((0, 0, 0, 0)),
+            due: Cell::new(wspec.interval().map(|_| Instant::now())),
+            stale: Cell::new(false),
+            wspec
         }
     }
 }
 
 pub trait WidgetSpec {
-    fn draw(&self, state: &mut state::State, widget: &Widget, context: Ctx);
+    /* None takes a share of whatever the fixed width widgets leave */
+    fn width(&self, state: &mut state::State, widget: &Widget) -> Option<u32>;
+    fn draw(&self, state: &mut state::State, widget: &Widget, rect: Rect, context: Ctx);
+    /* how often refresh is called, None for widgets redrawn only on retile and expose */
+    fn interval(&self) -> Option<Duration> { None }
+    fn refresh(&self) {}
+    /* new content arrived from another thread since the last call, see wake_bar */
+    fn updated(&self) -> bool { false }
+    /* x, y relative to the widget's rect */
+    fn on_click(&self, _: &mut state::State, __: &Widget, ___: u32, ____: (i32, i32)) {}
 }
 
+#[derive(Default)]
 pub struct TopBar {}
-pub struct TaskList {}
-pub struct Stats {}
-
+#[derive(Default)]
+pub struct TaskList {
+    pub entries: RefCell<Vec<(i32, i32, xlib::Window)>> /* drawn entries as (from x, to x, window) */
+}
 impl WidgetSpec for TopBar {
-    fn draw(&self, state: &mut state::State, widget: &Widget, context: Ctx) {
-        unsafe {
-            let screen_width: u32 = xlib::XDisplayWidth(state.dpy, state.screen) as u32;
-            let box_wh = STYLE.paddings.top;
-            let pad: i32 = text_width_px(state, widget.font, state.workspaces[0].tag) / 2;
-            
-            if context == Ctx::Expose {
-                xft::XftDrawRect(state.xft_draw, &state.colors.normal.bg, 0, 0, screen_width, STYLE.paddings.top);
-            }
-        
-            for i in 0..state.workspaces.len() {
-                let offset = i as u32 * box_wh;
-                let mut bgcol = &state.colors.normal.bg;
-                let mut fgcol = &state.colors.normal.fg;
-                if i == state.active.workspace { 
-                    bgcol = &state.colors.normal.fg;
-                    fgcol = &state.colors.normal.bg;
-                };
-                let utf8_string = CString::new(state.workspaces[i].tag).unwrap();
-                xft::XftDrawRect(state.xft_draw, bgcol, offset as i32, 0, box_wh, box_wh);
-                xft::XftDrawStringUtf8(state.xft_draw, fgcol, widget.font, offset as i32 + pad, box_wh as i32 - pad, utf8_string.as_ptr() as *const u8, utf8_string.to_bytes().len() as i32);
-            }
-        } 
+    fn width(&self, state: &mut state::State, _: &Widget) -> Option<u32> {
+        Some(state.workspaces.len() as u32 * state.bar_height)
+    }
+
+    fn draw(&self, state: &mut state::State, widget: &Widget, rect: Rect, _: Ctx) {
+        let box_wh = state.bar_height;
+        let baseline = text_baseline(widget.font, box_wh);
+
+        for i in 0..state.workspaces.len() {
+            let offset = rect.0 + (i as u32 * box_wh) as i32;
+            let (bgcol, fgcol) = if i == state.active.workspace {
+                (state.colors.normal.fg, state.colors.normal.bg)
+            } else {
+                (state.colors.normal.bg, state.colors.normal.fg)
+            };
+            let tag = state.workspaces[i].tag;
+            let pad = (box_wh as i32 - text_width_px(state, widget.font, tag)) / 2;
+            unsafe { xft::XftDrawRect(state.xft_draw, &bgcol, offset, 0, box_wh, box_wh) };
+            draw_text(state, state.xft_draw, fgcol, widget.font, offset + pad, baseline, tag);
+        }
+    }
+
+    fn on_click(&self, state: &mut state::State, _: &Widget, button: u32, (x, _): (i32, i32)) {
+        let box_wh = state.bar_height as i32;
+        if x < 0 || x >= state.workspaces.len() as i32 * box_wh { return }
+        let workspace = (x / box_wh) as usize;
+        match button {
+            xlib::Button1 => state.goto_workspace(workspace),
+            xlib::Button3 => state.send_active_window_to_workspace(workspace),
+            xlib::Button4 => state.prev_workspace(),
+            xlib::Button5 => state.next_workspace(),
+            _ => {}
+        }
     }
 }
 
 impl WidgetSpec for TaskList {
-    fn draw(&self, _: &mut state::State, __: &Widget, ___: Ctx) {}
-}
+    fn width(&self, _: &mut state::State, _: &Widget) -> Option<u32> {
+        None
+    }
 
-lazy_static! {
-    static ref SYS: Mutex<System> = Mutex::new(System::new_all());
-}
+    fn draw(&self, state: &mut state::State, widget: &Widget, rect: Rect, _: Ctx) {
+        let windows = state.workspaces[state.active.workspace].windows.clone();
+        let height = state.bar_height;
+        let mut entries = self.entries.borrow_mut();
+        entries.clear();
+
+        unsafe { xft::XftDrawRect(state.xft_draw, &state.colors.normal.bg, rect.0, 0, rect.2, height) };
+        if windows.is_empty() || rect.2 == 0 { return }
 
-impl WidgetSpec for Stats {
-    fn draw(&self, state: &mut state::State, widget: &Widget, context: Ctx) {
-        if context != Ctx::Expose { return }
-        let mut sys = SYS.lock().unwrap();
-        let screen_width = unsafe { xlib::XDisplayWidth(state.dpy, state.screen) as i32 };
-        sys.refresh_all();
+        let pad = text_width_px(state, widget.font, "A") / 2;
+        let baseline = text_baseline(widget.font, height);
+        let entry_width = rect.2 as i32 / windows.len() as i32;
 
-        let mem_usg = (sys.used_memory() as f32 / sys.total_memory() as f32) * 100f32;
+        for (i, window) in windows.iter().enumerate() {
+            let x = i as i32 * entry_width;
+            let title = window.get_title(state);
+            let title = ellipsize(state, widget.font, &title, entry_width - pad * 2);
+            let (bgcol, fgcol) = if *window == state.active.window {
+                (state.colors.normal.fg, state.colors.normal.bg)
+            } else if state.is_minimised(*window) {
+                (state.colors.normal.bg, state.colors.dimmed.fg)
+            } else {
+                (state.colors.normal.bg, state.colors.normal.fg)
+            };
 
-        let stats = [
-            format!("{:02} % CPU", sys.global_cpu_usage() as u32),
-            format!("{}", Local::now()),
-            format!("{:02} % ({} / {} MiB)", mem_usg as u32, sys.used_memory() / 1024 / 1024, sys.total_memory() / 1024 / 1024)
-        ];
+            unsafe { xft::XftDrawRect(state.xft_draw, &bgcol, rect.0 + x, 0, entry_width as u32, height) };
+            draw_text(state, state.xft_draw, fgcol, widget.font, rect.0 + x + pad, baseline, &title);
+            entries.push((x, x + entry_width, *window));
+        }
+    }
+
+    fn on_click(&self, state: &mut state::State, _: &Widget, button: u32, (x, _): (i32, i32)) {
+        let hit = self.entries.borrow().iter().find(|(from, to, _)| x >= *from && x < *to).map(|(_, _, w)| *w);
+        let window = match hit {
+            Some(window) => window,
+            None => return
+        };
+        match button {
+            xlib::Button1 if state.is_minimised(window) => state.restore(window),
+            xlib::Button1 => {
+                state.focus(window);
+                state.retile();
+            },
+            xlib::Button2 => {
+                state.active.window = window;
+                state.close_active();
+            },
+            _ => {}
+        }
+    }
+}
+
+impl state::State<'_> {
+    /* draws every bar, on a tick only the stale widgets unless something moved */
+    pub fn draw_bar(&mut self, context: Ctx) {
+        for no in 0..self.bars.len() {
+            self.draw_bar_on(no, context);
+        }
+        for widget in unsafe { WIDGETS.iter() } {
+            widget.stale.set(false);
+        }
+    }
 
-        let mut offset = 0;
-        let brk = text_width_px(state, widget.font, "A");
-        let pad = brk / 2;
+    /* lays the widgets out into the slots of one bar and draws them there */
+    pub fn draw_bar_on(&mut self, no: usize, context: Ctx) {
+        let widgets = unsafe { &WIDGETS };
+        let font = match widgets.first() {
+            Some(widget) => widget.font,
+            None => return
+        };
+        self.bar = no;
+        self.xft_draw = self.bars[no].xft_draw;
+        let bar_width = self.bars[no].rect.2;
+        let height = self.bar_height;
+        let separator_width = if STYLE.bar.separator.is_empty() { 0 } else { text_width_px(self, font, STYLE.bar.separator) };
 
-        for stat in stats.iter() {
-            let w = text_width_px(state, widget.font, stat.as_str());
-            offset += w;
-            offset += brk;
+        let desired: Vec<(Slot, Option<u32>)> = widgets.iter().map(|w| (w.slot, w.wspec.width(self, w))).collect();
+        let placed: Vec<Rect> = layout::bar(bar_width, &desired, separator_width as u32).into_iter().map(|(x, w)| (x, 0, w, height)).collect();
+        let full = context != Ctx::Tick || self.bars[no].widget_rects != placed;
+        let mut damage: Vec<Rect> = Vec::new();
 
-            unsafe {
-                let utf8_string: CString = CString::new((*stat).clone()).unwrap();
-                xft::XftDrawRect(state.xft_draw, &state.colors.normal.fg, screen_width - offset - brk, 0, (brk + w + brk) as u32, STYLE.paddings.top);
-                xft::XftDrawStringUtf8(state.xft_draw, &state.colors.normal.bg, widget.font, screen_width - offset, STYLE.paddings.top as i32 - pad, utf8_string.as_ptr() as *const u8, utf8_string.to_bytes().len() as i32);
+        if full {
+            unsafe { xft::XftDrawRect(self.xft_draw, &self.colors.normal.bg, 0, 0, bar_width, height) };
+            damage.push((0, 0, bar_width, height));
+        }
+
+        for (i, (widget, rect)) in widgets.iter().zip(placed.iter()).enumerate() {
+            widget.rect.set(*rect);
+            if !full && !widget.stale.get() { continue }
+            let (x, w) = (rect.0, rect.2);
+            if !full {
+                /* whatever a shorter text leaves behind */
+                unsafe { xft::XftDrawRect(self.xft_draw, &self.colors.normal.bg, x, 0, w, height) };
+                damage.push(*rect);
             }
+            widget.draw(self, context);
+
+            let last_in_slot = !desired[i + 1..].iter().any(|(slot, w)| *slot == widget.slot && *w != Some(0));
+            if separator_width == 0 || desired[i].1 == Some(0) || last_in_slot { continue }
+            let baseline = text_baseline(font, height);
+            unsafe { xft::XftDrawRect(self.xft_draw, &self.colors.normal.bg, x + w as i32, 0, separator_width as u32, height) };
+            draw_text(self, self.xft_draw, self.colors.normal.fg, font, x + w as i32, baseline, STYLE.bar.separator);
+        }
+        self.bars[no].widget_rects = placed;
+
+        for rect in damage {
+            self.present_bar(no, rect);
         }
     }
-}
 
-pub fn widget_refresh() {
+    /* bar height from the tallest widget font plus the padding above and below */
+    pub fn measure_bar(&mut self) -> u32 {
+        /* a glyph drawn from a fallback font has to fit as well */
+        let fonts = unsafe { WIDGETS.iter() }.map(|w| w.font).chain(self.fallback_fonts.iter().copied());
+        let text_height = fonts
+            .filter(|font| !font.is_null())
+            .map(|font| unsafe { ((*font).ascent + (*font).descent) as u32 })
+            .max()
+            .unwrap_or(0);
+        (text_height + STYLE.bar.padding * 2).max(1)
+    }
 
-    println!("Refresh!");
-    unsafe {
-        let dpy: *mut xlib::_XDisplay = xlib::XOpenDisplay(ptr::null());
-        let root_window: u64 = xlib::XDefaultRootWindow(dpy);
-        
-        let mut event = xlib::XEvent {
-            expose: xlib::XExposeEvent {
-                type_: xlib::Expose,
-                serial: 0,
-                send_event: 0,
-                display: ptr::null_mut(),
-                window: 69,
-                x: 0,
-                y: 0,
-                width: 0,
-                height: 0,
-                count: 0,
+    /* (re)creates the off-screen copy of a bar when the bar changes size */
+    pub fn fit_bar_buffer(&mut self, no: usize) {
+        let bar = &mut self.bars[no];
+        let (width, height) = (bar.rect.2, bar.rect.3);
+        if bar.buffer_size == (width, height) { return }
+        unsafe {
+            if !bar.xft_draw.is_null() { xft::XftDrawDestroy(bar.xft_draw); }
+            if bar.buffer != 0 { xlib::XFreePixmap(self.dpy, bar.buffer); }
+            let depth = xlib::XDefaultDepth(self.dpy, self.screen) as u32;
+            bar.buffer = xlib::XCreatePixmap(self.dpy, bar.window, width, height, depth);
+            bar.xft_draw = xft::XftDrawCreate(self.dpy, bar.buffer,
+                xlib::XDefaultVisual(self.dpy, self.screen), xlib::XDefaultColormap(self.dpy, self.screen));
+            xft::XftDrawRect(bar.xft_draw, &self.colors.normal.bg, 0, 0, width, height);
+            /* tray icons with a parent-relative background show the bar through */
+            xlib::XSetWindowBackgroundPixmap(self.dpy, bar.window, bar.buffer);
+        }
+        bar.buffer_size = (width, height);
+        bar.widget_rects.clear();
+        if self.bar == no { self.xft_draw = bar.xft_draw; }
+    }
+
+    /* copies a part of the off-screen copy of a bar onto its window */
+    pub fn present_bar(&mut self, no: usize, rect: Rect) {
+        if rect.2 == 0 || rect.3 == 0 { return }
+        let bar = &self.bars[no];
+        unsafe { xlib::XCopyArea(self.dpy, bar.buffer, bar.window, self.gc, rect.0, rect.1, rect.2, rect.3, rect.0, rect.1) };
+    }
+
+    /* how long the event loop may sleep before some widget is due */
+    pub fn widget_timeout(&self) -> Option<Duration> {
+        let now = Instant::now();
+        unsafe { WIDGETS.iter() }
+            .filter_map(|w| w.due.get())
+            .min()
+            .map(|due| due.saturating_duration_since(now))
+    }
+
+    /* refreshes the widgets that are due or were updated from elsewhere and redraws them */
+    pub fn tick_widgets(&mut self) {
+        let now = Instant::now();
+        let mut any = false;
+        for widget in unsafe { WIDGETS.iter() } {
+            if widget.wspec.updated() {
+                widget.stale.set(true);
+                any = true;
             }
-        };
+            let interval = match (widget.due.get(), widget.wspec.interval()) {
+                (Some(due), Some(interval)) if due <= now => interval,
+                _ => continue
+            };
+            widget.wspec.refresh();
+            widget.due.set(Some(now + interval));
+            widget.stale.set(true);
+            any = true;
+        }
+        if any { self.draw_widgets(Ctx::Tick); }
+    }
 
-        xlib::XSendEvent(dpy, root_window, 0, ExposureMask, &mut event);
-        xlib::XFlush(dpy);
+    /* x, y relative to the bar */
+    pub fn bar_click(&mut self, no: usize, button: u32, (x, y): (i32, i32)) {
+        /* the widgets remember the bar drawn last, the click has to hit the layout of its own */
+        if self.bar != no { self.draw_bar_on(no, Ctx::Expose); }
+        let hit = unsafe { WIDGETS.iter() }.find(|w| {
+            let rect = w.rect.get();
+            x >= rect.0 && x < rect.0 + rect.2 as i32
+        });
+        if let Some(widget) = hit {
+            let rect = widget.rect.get();
+            widget.wspec.on_click(self, widget, button, (x - rect.0, y - rect.1));
+        }
     }
+}
+
+lazy_static! {
+    /* written to by threads feeding widgets, so the event loop wakes up and redraws them */
+    static ref WAKE: (i32, i32) = {
+        let mut fds = [0; 2];
+        unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_NONBLOCK | libc::O_CLOEXEC) };
+        (fds[0], fds[1])
+    };
+}
+
+pub fn wake_fd() -> i32 {
+    WAKE.0
+}
+
+pub fn wake_bar() {
+    unsafe { libc::write(WAKE.1, [0u8].as_ptr() as *const libc::c_void, 1) };
+}
 
+pub fn drain_wake() {
+    let mut buf = [0u8; 64];
+    while unsafe { libc::read(WAKE.0, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) } > 0 {}
 }
 
-pub fn widget_window(dpy: *mut xlib::Display ) -> (xlib::Window, *mut xft::XftDraw)  {
+/* the window of a bar, placed and mapped by place_bar */
+pub fn widget_window(dpy: *mut xlib::Display ) -> xlib::Window {
     unsafe {
         let screen = xlib::XDefaultScreen(dpy);
         let root: u64 = xlib::XRootWindow(dpy, screen);
         let screen_width: u32 = xlib::XDisplayWidth(dpy, screen) as u32;
-        let _screen_height = xlib::XDisplayHeight(dpy, screen) as u32;
 
         let mut wa : XSetWindowAttributes = mem::zeroed();
         wa.override_redirect = xlib::True;
         wa.background_pixmap = xlib::ParentRelative as u64;
         wa.background_pixel = 0;
-        wa.event_mask = xlib::ButtonPressMask | xlib::ExposureMask;
+        wa.event_mask = xlib::ButtonPressMask | xlib::ExposureMask | xlib::LeaveWindowMask;
 
-        let win = xlib::XCreateWindow(dpy, root, 0, 0, screen_width, STYLE.paddings.top, 0, xlib::XDefaultDepth( dpy, screen),
+        xlib::XCreateWindow(dpy, root, 0, 0, screen_width, 1, 0, xlib::XDefaultDepth( dpy, screen),
         xlib::CopyFromParent as u32, xlib::XDefaultVisual(dpy, screen),
-        xlib::CWEventMask, &mut wa);
-        xlib::XMapWindow(dpy, win);
+        xlib::CWEventMask, &mut wa)
+    }
+}
+
+/* the gc copying the off-screen bars onto their windows, see present_bar */
+pub fn bar_gc(dpy: *mut xlib::Display) -> xlib::GC {
+    unsafe {
+        let root = xlib::XDefaultRootWindow(dpy);
+        let gc = xlib::XCreateGC(dpy, root, 0, ptr::null_mut());
+        xlib::XSetGraphicsExposures(dpy, gc, xlib::False);
+        gc
+    }
+}
+
+pub fn open_font(state: &mut state::State, name: &str) -> *mut xft::XftFont {
+    let name = CString::new(name).unwrap();
+    unsafe { xft::XftFontOpenName(state.dpy, state.screen, name.as_ptr()) }
+}
+
+/* the glyphs of the string grouped into runs, each with the first of font and the fallback fonts having them */
+fn font_runs(state: &mut state::State, font: *mut xft::XftFont, string: &str) -> Vec<(*mut xft::XftFont, String)> {
+    let dpy: *mut xlib::Display = state.dpy;
+    let mut runs: Vec<(*mut xft::XftFont, String)> = Vec::new();
+    for c in string.chars() {
+        let has = |f: *mut xft::XftFont| unsafe { xft::XftCharExists(dpy, f, c as u32) } != 0;
+        let chosen = if has(font) { font } else { state.fallback_fonts.iter().copied().find(|f| has(*f)).unwrap_or(font) };
+        match runs.last_mut() {
+            Some((f, run)) if *f == chosen => run.push(c),
+            _ => runs.push((chosen, c.to_string()))
+        }
+    }
+    runs
+}
 
-        let xft_draw = xft::XftDrawCreate(dpy, win, xlib::XDefaultVisual(dpy, screen), xlib::XDefaultColormap(dpy, screen));
-        return (win, xft_draw)
+/* vertically centres the font's ascent and descent in the height */
+pub fn text_baseline(font: *mut xft::XftFont, height: u32) -> i32 {
+    unsafe { (height as i32 + (*font).ascent - (*font).descent) / 2 }
+}
+
+pub fn draw_text(state: &mut state::State, draw: *mut xft::XftDraw, color: xft::XftColor, font: *mut xft::XftFont, x: i32, y: i32, string: &str) {
+    let mut x = x;
+    for (font, run) in font_runs(state, font, string) {
+        let utf8_string = CString::new(run.as_str()).unwrap_or_default();
+        unsafe { xft::XftDrawStringUtf8(draw, &color, font, x, y, utf8_string.as_ptr() as *const u8, utf8_string.to_bytes().len() as i32) };
+        x += run_width(state, font, &run);
     }
 }
 
-fn text_width_px(state: &mut state::State, font: *mut xft::XftFont, string: &str) -> i32 {
+fn run_width(state: &mut state::State, font: *mut xft::XftFont, string: &str) -> i32 {
     unsafe {
         let mut extents: xrender::XGlyphInfo = std::mem::zeroed();
-        let utf8_string = CString::new(string).unwrap();
-        // Calculate the text extents
+        let utf8_string = CString::new(string).unwrap_or_default();
         xft::XftTextExtentsUtf8(
             state.dpy,
             font,
@@ -178,7 +400,23 @@ fn text_width_px(state: &mut state::State, font: *mut xft::XftFont, string: &str
             utf8_string.to_bytes().len() as i32,
             &mut extents,
         );
+        extents.xOff as i32
+    }
+}
+
+/* advance width, fallback fonts included */
+pub fn text_width_px(state: &mut state::State, font: *mut xft::XftFont, string: &str) -> i32 {
+    font_runs(state, font, string).iter().map(|(font, run)| run_width(state, *font, run)).sum()
+}
 
-        return extents.width as i32;
-    }   
-}
\ No newline at end of file
+/* cuts the string down to max_px, marking the cut with an ellipsis */
+pub fn ellipsize(state: &mut state::State, font: *mut xft::XftFont, string: &str, max_px: i32) -> String {
+    if text_width_px(state, font, string) <= max_px { return string.to_string() }
+    let mut chars: Vec<char> = string.chars().collect();
+    while !chars.is_empty() {
+        chars.pop();
+        let candidate = format!("{}…", chars.iter().collect::<String>());
+        if text_width_px(state, font, &candidate) <= max_px { return candidate }
+    }
+    String::new()
+}
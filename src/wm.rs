@@ -1,7 +1,10 @@
 use x11::xlib::{self, XGrabServer};
-use std::mem;
+use std::ffi::{CStr, CString};
+use std::{mem, ptr, slice};
 
-use crate::{config::{CustomData, STYLE}, state::{self, WIDGETS}, widgets::Ctx};
+use serde_json::json;
+
+use crate::{config::{CustomData, MONITORS, STYLE}, layout::{self, Rect}, state, style::{BarPosition, Paddings}, widgets::Ctx};
 
 pub struct Space<'a> {
     pub tag: &'a str,
@@ -9,6 +12,14 @@ pub struct Space<'a> {
     pub custom: Option<CustomData> /* custom config for active workspace*/
 }
 
+#[derive(PartialEq, Clone, Copy)]
+pub enum Dir {
+    Left,
+    Right,
+    Up,
+    Down
+}
+
 pub struct _Tile {
     pub coords: (i32, i32),
     pub size: (u32, u32)
@@ -31,44 +42,203 @@ macro_rules! active_workspace_wins {
 impl state::State<'_> {
 
     pub fn focus(&mut self, window: xlib::Window){
-        unsafe { xlib::XRaiseWindow(self.dpy, window) };       
+        unsafe { xlib::XRaiseWindow(self.dpy, window.outer(self)) };       
         self.active.window = window;
     }
 
     pub fn focus_next(&mut self){
-        let len =  active_workspace_wins!(self).len();
+        let windows = self.visible_windows();
+        let len = windows.len();
         if len == 0 { return; }
 
-        if let Some(ix) = active_workspace_wins!(self).iter().position(|w| *w == self.active.window) {
-            self.active.window = active_workspace_wins!(self)[(ix+1)%len];
+        if let Some(ix) = windows.iter().position(|w| *w == self.active.window) {
+            self.active.window = windows[(ix+1)%len];
         } else {
-            self.active.window = active_workspace_wins!(self)[len - 1];
+            self.active.window = windows[len - 1];
         }
 
         self.retile();
     }
 
     pub fn focus_previous(&mut self){
-        let len =  active_workspace_wins!(self).len();
+        let windows = self.visible_windows();
+        let len = windows.len();
         if len == 0 { return; }
 
-        if let Some(ix) = active_workspace_wins!(self).iter().position(|w| *w == self.active.window) {
+        if let Some(ix) = windows.iter().position(|w| *w == self.active.window) {
             let mut previous: usize = len - 1;
             if ix != 0 { previous = ix - 1; } 
-            self.active.window = active_workspace_wins!(self)[previous];
+            self.active.window = windows[previous];
         } else {
-            self.active.window = active_workspace_wins!(self)[0];
+            self.active.window = windows[0];
         }
 
         self.retile();
     }
 
+    /* windows of the active workspace that aren't minimised */
+    pub fn visible_windows(&self) -> Vec<xlib::Window> {
+        active_workspace_wins!(self).iter().filter(|w| !self.is_minimised(**w)).copied().collect()
+    }
+
+    pub fn is_minimised(&self, window: xlib::Window) -> bool {
+        active_workspace!(self).custom.as_ref().is_some_and(|c| c.minimised.contains(&window))
+    }
+
+    pub fn focus_direction(&mut self, dir: Dir){
+        if let Some(window) = self.window_in_direction(dir) {
+            self.focus(window);
+            self.retile();
+        }
+    }
+
+    pub fn swap_direction(&mut self, dir: Dir){
+        let target = match self.window_in_direction(dir) {
+            Some(window) => window,
+            None => return
+        };
+        if let Some(custom) = &active_workspace!(self).custom {
+            let not_tiled = |w: &xlib::Window| custom.floating_windows.contains(w) || custom.fullscreen_windows.contains(w);
+            if not_tiled(&target) || not_tiled(&self.active.window) { return }
+        }
+        let maybe_ix1 = active_workspace_wins!(self).iter().position(|x| *x == self.active.window);
+        let maybe_ix2 = active_workspace_wins!(self).iter().position(|x| *x == target);
+        if let (Some(ix1), Some(ix2)) = (maybe_ix1, maybe_ix2) {
+            active_workspace_wins!(self).swap(ix1, ix2);
+            self.retile();
+            self.emit_layout();
+        }
+    }
+
+    /* indices into the Space of the windows retile tiles - not minimised, floating or fullscreen */
+    pub fn tiled_indices(&self) -> Vec<usize> {
+        let custom = active_workspace!(self).custom.as_ref();
+        active_workspace_wins!(self).iter().enumerate()
+            .filter(|(_, w)| !custom.is_some_and(|c| c.minimised.contains(w) || c.floating_windows.contains(w) || c.fullscreen_windows.contains(w)))
+            .map(|(ix, _)| ix)
+            .collect()
+    }
+
+    /* a single tiled window, floating ones aside - it loses both its gaps and its border */
+    pub fn lone_tiled(&self) -> bool {
+        self.tiled_indices().len() == 1
+    }
+
+    /* master is the last tiled window of the Space, the stack follows from the first one downwards */
+    pub fn zoom(&mut self){
+        let tiled = self.tiled_indices();
+        if let Some(ix) = tiled.iter().copied().find(|ix| active_workspace_wins!(self)[*ix] == self.active.window) {
+            active_workspace_wins!(self).swap(ix, tiled[tiled.len() - 1]);
+            self.retile();
+            self.emit_layout();
+        }
+    }
+
+    pub fn move_in_stack(&mut self, delta: i32){
+        let tiled = self.tiled_indices();
+        let len = tiled.len();
+        if let Some(k) = tiled.iter().position(|ix| active_workspace_wins!(self)[*ix] == self.active.window) {
+            /* position in the tiling order: master first, then the stack top to bottom */
+            let tile_ix = (k + 1) % len;
+            let new_tile_ix = (tile_ix as i32 + delta).rem_euclid(len as i32) as usize;
+            active_workspace_wins!(self).swap(tiled[k], tiled[(new_tile_ix + len - 1) % len]);
+            self.retile();
+            self.emit_layout();
+        }
+    }
+
+    pub fn rotate_stack(&mut self, clockwise: bool){
+        let tiled = self.tiled_indices();
+        if tiled.is_empty() { return }
+        let mut windows: Vec<xlib::Window> = tiled.iter().map(|ix| active_workspace_wins!(self)[*ix]).collect();
+        if clockwise {
+            windows.rotate_right(1);
+        } else {
+            windows.rotate_left(1);
+        }
+        for (ix, window) in tiled.into_iter().zip(windows) {
+            active_workspace_wins!(self)[ix] = window;
+        }
+        self.retile();
+        self.emit_layout();
+    }
+
+    /* nearest visible window in the given direction on the same monitor, then on the adjacent monitor that way, */
+    /* then wrapping around to the monitor at the other end                                                      */
+    fn window_in_direction(&mut self, dir: Dir) -> Option<xlib::Window> {
+        let windows = self.visible_windows();
+        if !windows.contains(&self.active.window) { return None }
+        let from = self.active.window.get_rect(self);
+        let active = self.active.window;
+        let candidates: Vec<(xlib::Window, (i32, i32, u32, u32))> = windows
+            .into_iter()
+            .filter(|w| *w != active)
+            .map(|w| (w, w.get_rect(self)))
+            .collect();
+
+        let monitor = self.monitor_of(from);
+        let on_monitor: Vec<(Rect, Vec<(xlib::Window, Rect)>)> = self.monitors().into_iter().map(|m| {
+            let windows = candidates.iter().filter(|(_, rect)| self.monitor_of(*rect) == m).copied().collect();
+            (m, windows)
+        }).collect();
+        let windows_on = |m: Rect| on_monitor.iter().find(|(other, _)| *other == m).map(|(_, w)| w.clone()).unwrap_or_default();
+
+        let nearest = windows_on(monitor).into_iter()
+            .filter_map(|(w, rect)| direction_score(from, rect, dir).map(|score| (score, w)))
+            .min_by_key(|(score, _)| *score)
+            .map(|(_, w)| w);
+        if nearest.is_some() { return nearest }
+
+        /* the closest monitor holding windows that way, or wrapping, the furthest one the other way */
+        let occupied: Vec<Rect> = on_monitor.iter().filter(|(m, w)| *m != monitor && !w.is_empty()).map(|(m, _)| *m).collect();
+        let next = occupied.iter()
+            .filter_map(|m| direction_score(monitor, *m, dir).map(|score| (score, *m)))
+            .min_by_key(|(score, _)| *score)
+            .or_else(|| occupied.iter()
+                .filter_map(|m| direction_score(monitor, *m, dir.opposite()).map(|score| (score, *m)))
+                .max_by_key(|(score, _)| *score))
+            .map(|(_, m)| m)?;
+
+        /* entering it from the edge we came through */
+        let entry = match dir {
+            Dir::Left => (next.0 + next.2 as i32, from.1, 0, from.3),
+            Dir::Right => (next.0 - 1, from.1, 0, from.3),
+            Dir::Up => (from.0, next.1 + next.3 as i32, from.2, 0),
+            Dir::Down => (from.0, next.1 - 1, from.2, 0)
+        };
+        windows_on(next).into_iter()
+            .map(|(w, rect)| (direction_score(entry, rect, dir).unwrap_or(i64::MAX), w))
+            .min_by_key(|(score, _)| *score)
+            .map(|(_, w)| w)
+    }
+
+    pub fn monitors(&mut self) -> Vec<(i32, i32, u32, u32)> {
+        if !MONITORS.is_empty() { return MONITORS.to_vec() }
+        if !self.detected_monitors.is_empty() { return self.detected_monitors.clone() }
+        let screen_width: u32 = unsafe{xlib::XDisplayWidth(self.dpy, self.screen) as u32};
+        let screen_height = unsafe{xlib::XDisplayHeight(self.dpy, self.screen) as u32};
+        vec![(0, 0, screen_width, screen_height)]
+    }
+
+    /* the monitor holding the center of the rect, the first one if none does */
+    pub fn monitor_of(&mut self, rect: (i32, i32, u32, u32)) -> (i32, i32, u32, u32) {
+        let (x, y) = (rect.0 + rect.2 as i32 / 2, rect.1 + rect.3 as i32 / 2);
+        let monitors = self.monitors();
+        *monitors.iter()
+            .find(|m| x >= m.0 && x < m.0 + m.2 as i32 && y >= m.1 && y < m.1 + m.3 as i32)
+            .unwrap_or(&monitors[0])
+    }
+
     fn set_workspace(&mut self, no: usize){
-        for window in active_workspace_wins!(self).iter() {
-            unsafe { xlib::XUnmapWindow(self.dpy, *window) };
+        for window in active_workspace_wins!(self).clone() {
+            window.do_unmap(self);
         }
 
         self.active.workspace = no;
+        if !active_workspace_wins!(self).contains(&self.active.window) {
+            self.active.window = active_workspace_wins!(self).last().copied().unwrap_or(self.root);
+        }
+        self.emit("workspace", |state| json!({ "workspace": no, "tag": state.workspaces[no].tag }));
         self.retile();
     }
 
@@ -85,28 +255,71 @@ impl state::State<'_> {
         self.set_workspace(new_no);
     }
 
+    /* nothing is focused - root after the last window went away, 0 after the active one was sent off */
+    pub fn no_active_window(&self) -> bool {
+        self.active.window == self.root || self.active.window == 0
+    }
+
+    /* whether the active window belongs to the active Space - a shown scratchpad is active without */
+    pub fn active_in_space(&self) -> bool {
+        active_workspace_wins!(self).contains(&self.active.window)
+    }
+
     pub fn send_active_window_to_workspace(&mut self, workspace_no: usize) {
-        if workspace_no >= self.workspaces.len() { return }
+        if workspace_no >= self.workspaces.len() || !self.active_in_space() { return }
         active_workspace_wins!(self).retain(|x| *x != self.active.window);
-        unsafe { xlib::XUnmapWindow(self.dpy, self.active.window) };
+        self.active.window.do_unmap(self);
         self.workspaces[workspace_no].windows.push(self.active.window);
+        let window = self.active.window;
+        self.emit("window", |_| json!({ "change": "moved", "workspace": workspace_no, "id": window }));
         self.active.window = 0;
         self.retile();
     }
 
+    /* drops a destroyed window from whichever Space still holds it */
+    pub fn forget_window(&mut self, window: xlib::Window) {
+        let workspace = match self.workspaces.iter().position(|space| space.windows.contains(&window)) {
+            Some(workspace) => workspace,
+            None => return
+        };
+        let space = &mut self.workspaces[workspace];
+        space.windows.retain(|w| *w != window);
+        if let Some(custom) = &mut space.custom {
+            custom.minimised.retain(|w| *w != window);
+            custom.floating_windows.remove(&window);
+            custom.fullscreen_windows.remove(&window);
+            custom.borders.remove(&window);
+        }
+        self.scratchpads.retain(|_, w| *w != window);
+        self.undecorate(window);
+        self.emit("window", |_| json!({ "change": "destroyed", "id": window }));
+        if workspace != self.active.workspace { return }
+        if window == self.active.window {
+            self.active.window = self.root;
+            self.focus_next();
+        }
+        self.retile();
+    }
+
     pub fn goto_workspace(&mut self, workspace_no: usize){
         if workspace_no >= self.workspaces.len() { return }
         self.set_workspace(workspace_no);
     }
 
     pub fn close_active(&mut self){
-        if active_workspace_wins!(self).is_empty() { return; }
+        if !self.active_in_space() { return; }
         active_workspace_wins!(self).retain(|x| *x != self.active.window);
         self.retile();
         self.kill_window_process(self.active.window);
     }
 
+    pub fn atom(&mut self, name: &str) -> xlib::Atom {
+        let name = CString::new(name).unwrap();
+        unsafe { xlib::XInternAtom(self.dpy, name.as_ptr(), xlib::False) }
+    }
+
     fn window_exists(&self, window: xlib::Window) -> bool {
+        if self.scratchpads.values().any(|w| *w == window) { return true; }
         for workspace in self.workspaces.iter() {
             for win in workspace.windows.iter() {
                 if *win == window { return true; }
@@ -156,60 +369,105 @@ impl state::State<'_> {
     pub fn cascade_autotiling(&mut self, windows: Vec<xlib::Window>){
 
         for window in windows.iter() {
-            unsafe { xlib::XLowerWindow(self.dpy, *window) };       
+            unsafe { xlib::XLowerWindow(self.dpy, window.outer(self)) };       
         }
 
-        let useless_gap: u32 = STYLE.useless_gap;
-        let border = STYLE.border_thickness;
-        let screen_width: u32 = unsafe{xlib::XDisplayWidth(self.dpy, self.screen) as u32};
-        let screen_height = unsafe{xlib::XDisplayHeight(self.dpy, self.screen) as u32};
-        
-        let maybe_latest_window: Option<&u64> = windows.last();
-        if maybe_latest_window.is_none() { return };
-        
-        let latest_window = maybe_latest_window.unwrap();
-        if windows.len() == 1 {
-            latest_window.do_map(self, (
-                (useless_gap + STYLE.paddings.left) as i32, (useless_gap + STYLE.paddings.top) as i32, 
-                screen_width - useless_gap * 2 - border * 2 - STYLE.paddings.left - STYLE.paddings.right, screen_height - useless_gap * 2 - border * 2 - STYLE.paddings.top - STYLE.paddings.bottom
-            ));
-            return;
-        } 
+        if windows.is_empty() { return }
 
-        let mut middle = screen_width / 2;
+        let mut outer_gap = STYLE.outer_gap;
+        let mut inner_gap = STYLE.inner_gap;
+        let mut middle = unsafe{xlib::XDisplayWidth(self.dpy, self.screen) as u32} / 2;
         if let Some(custom) = &active_workspace!(self).custom {
+            outer_gap = custom.outer_gap;
+            inner_gap = custom.inner_gap;
             middle = custom.separator;
         }
-        
-        latest_window.do_map(self, (
-            (useless_gap + STYLE.paddings.left) as i32, (useless_gap  + STYLE.paddings.top) as i32,
-            middle - useless_gap * 2 - border * 2 - STYLE.paddings.left, screen_height - useless_gap * 2 - border * 2 - STYLE.paddings.top - STYLE.paddings.bottom
-        ));
+        if STYLE.smart_gaps && self.lone_tiled() {
+            outer_gap = Paddings { top: 0, bottom: 0, left: 0, right: 0 };
+        }
 
-        let len_rest = windows.len() - 1;
-        let increment = (screen_height - STYLE.paddings.top - STYLE.paddings.bottom) / len_rest as u32;
+        let area = layout::shrink(self.tiling_area(), &outer_gap);
+        let cells = layout::cascade(area, windows.len(), middle as i32, inner_gap);
+        /* the paddings and the outer gap are kept between a bar and the windows below (or above) it */
+        let bars = self.reserved_bar_rects();
+        let margin = match STYLE.bar.position {
+            BarPosition::Top => STYLE.paddings.top + outer_gap.top,
+            BarPosition::Bottom => STYLE.paddings.bottom + outer_gap.bottom
+        };
+        for (window, cell) in windows.iter().zip(cells) {
+            let cell = bars.iter().fold(cell, |cell, bar| layout::clear_of(cell, *bar, margin));
+            let border = self.border_for(*window);
+            window.do_map(self, layout::inset_border(cell, border));
+        }
+    }
 
-        for i in 0..len_rest {
-            let start_y = increment * i as u32 + useless_gap;
+    /* the screen without the paddings - the bars only cover their own monitors, the cells under */
+    /* them are moved clear of them one by one, see cascade_autotiling                            */
+    pub fn tiling_area(&mut self) -> layout::Rect {
+        let screen_width: u32 = unsafe{xlib::XDisplayWidth(self.dpy, self.screen) as u32};
+        let screen_height = unsafe{xlib::XDisplayHeight(self.dpy, self.screen) as u32};
+        layout::shrink((0, 0, screen_width, screen_height), &STYLE.paddings)
+    }
 
-            windows[i].do_map(self, (
-                (useless_gap / 2 + middle) as i32, (start_y + STYLE.paddings.top) as i32, 
-                (screen_width - middle) - useless_gap * 2 - border * 2 - STYLE.paddings.right, increment - useless_gap * 2 - border * 2
-            ));
+    /* border width of a window on the active workspace */
+    pub fn border_for(&self, window: xlib::Window) -> u32 {
+        if let Some(border) = active_workspace!(self).custom.as_ref().and_then(|c| c.borders.get(&window)) {
+            return *border;
+        }
+        if STYLE.smart_borders {
+            let fullscreen = active_workspace!(self).custom.as_ref().is_some_and(|c| c.fullscreen_windows.contains(&window));
+            let lone = self.lone_tiled() && self.tiled_indices().iter().any(|ix| active_workspace_wins!(self)[*ix] == window);
+            if fullscreen || lone { return 0 }
         }
+        STYLE.border_thickness
     }
 
     pub fn draw_widgets(&mut self, context: Ctx){
-        for widget in unsafe { WIDGETS.iter() } {
-            widget.draw(self, context.clone());
+        /* nothing to draw on before place_bar sized the bar */
+        if self.xft_draw.is_null() { return }
+        if self.picker.is_some() {
+            self.draw_picker();
+            return;
         }
-      
+        self.draw_bar(context);
     }
 }
 
+impl Dir {
+    pub fn opposite(self) -> Dir {
+        match self {
+            Dir::Left => Dir::Right,
+            Dir::Right => Dir::Left,
+            Dir::Up => Dir::Down,
+            Dir::Down => Dir::Up
+        }
+    }
+}
+
+/* distance between rect centers along dir, with the sideways offset weighted twice; None if `to` isn't in that direction */
+fn direction_score(from: (i32, i32, u32, u32), to: (i32, i32, u32, u32), dir: Dir) -> Option<i64> {
+    let center = |r: (i32, i32, u32, u32)| (r.0 as i64 + r.2 as i64 / 2, r.1 as i64 + r.3 as i64 / 2);
+    let (fx, fy) = center(from);
+    let (tx, ty) = center(to);
+    let (along, across) = match dir {
+        Dir::Left => (fx - tx, fy - ty),
+        Dir::Right => (tx - fx, fy - ty),
+        Dir::Up => (fy - ty, fx - tx),
+        Dir::Down => (ty - fy, fx - tx)
+    };
+    if along <= 0 { return None }
+    Some(along + across.abs() * 2)
+}
+
 pub trait WindowExt {
     fn do_map(self, state: &mut state::State, rect: (i32, i32, u32, u32));
+    fn do_unmap(self, state: &mut state::State);
     fn get_rect(self, state: &mut state::State) -> (i32, i32, u32, u32);
+    fn get_title(self, state: &mut state::State) -> String;
+    fn get_class(self, state: &mut state::State) -> (String, String);
+    fn get_role(self, state: &mut state::State) -> String;
+    fn get_window_type(self, state: &mut state::State) -> Option<String>;
+    fn outer(self, state: &mut state::State) -> xlib::Window;
 }
 
 impl WindowExt for xlib::Window {
@@ -221,10 +479,26 @@ impl WindowExt for xlib::Window {
 
         let mut wc: xlib::XWindowChanges = unsafe { mem::zeroed() };
         let mut border_col = state.colors.normal.border.pixel;
-        wc.border_width = STYLE.border_thickness as i32;
+        wc.border_width = state.border_for(self) as i32;
 
         if self == state.active.window { border_col = state.colors.selected.border.pixel; }
 
+        if let Some(frame) = state.decor.frames.get(&self).map(|f| f.window) {
+            let fullscreen = active_workspace!(state).custom.as_ref().is_some_and(|c| c.fullscreen_windows.contains(&self));
+            let title_h = if fullscreen { 0 } else { STYLE.titlebar.height.min(rect.3.saturating_sub(1)) };
+            unsafe {
+                xlib::XConfigureWindow(state.dpy, frame, xlib::CWBorderWidth.into(), &mut wc as *mut xlib::XWindowChanges);
+                xlib::XSetWindowBorder(state.dpy, frame, border_col);
+                xlib::XMoveResizeWindow(state.dpy, frame, rect.0, rect.1, rect.2, rect.3);
+                xlib::XMoveResizeWindow(state.dpy, self, 0, title_h as i32, rect.2, rect.3 - title_h);
+                xlib::XMapWindow(state.dpy, self);
+                xlib::XMapWindow(state.dpy, frame);
+                if self == state.active.window { xlib::XSetInputFocus(state.dpy, self, xlib::RevertToPointerRoot, xlib::CurrentTime); }
+            }
+            state.draw_titlebar(self);
+            return;
+        }
+
         unsafe {
             xlib::XConfigureWindow(state.dpy, self, xlib::CWBorderWidth.into(), &mut wc as *mut xlib::XWindowChanges);
             xlib::XSetWindowBorder(state.dpy, self, border_col);
@@ -236,10 +510,108 @@ impl WindowExt for xlib::Window {
         }
     }
 
+    fn do_unmap(self, state: &mut state::State){
+        let window = self.outer(state);
+        let mut wa : xlib::XWindowAttributes = unsafe { mem::zeroed() };
+        if unsafe { xlib::XGetWindowAttributes(state.dpy, window, &mut wa) } == 0 || wa.map_state == xlib::IsUnmapped { return }
+        /* a frame's unmap doesn't concern the client, only a client's own needs ignoring */
+        if window == self { state.ignore_unmap.insert(self); }
+        unsafe { xlib::XUnmapWindow(state.dpy, window) };
+    }
+
     fn get_rect(self, state: &mut state::State) -> (i32, i32, u32, u32) {
+        let window = self.outer(state);
         let mut wa : xlib::XWindowAttributes = unsafe { mem::zeroed() };
-        if( unsafe { xlib::XGetWindowAttributes(state.dpy, self, &mut wa) } == 0) { return (0,0,0,0); };
+        if( unsafe { xlib::XGetWindowAttributes(state.dpy, window, &mut wa) } == 0) { return (0,0,0,0); };
         return (wa.x, wa.y, wa.width as u32, wa.height as u32)
     }
+
+    /* _NET_WM_NAME, falling back to WM_NAME */
+    fn get_title(self, state: &mut state::State) -> String {
+        let net_wm_name = state.atom("_NET_WM_NAME");
+        let utf8_string = state.atom("UTF8_STRING");
+        if let Some(title) = text_property(state, self, net_wm_name, utf8_string).filter(|t| !t.is_empty()) {
+            return title;
+        }
+
+        let mut name: *mut i8 = ptr::null_mut();
+        unsafe {
+            if xlib::XFetchName(state.dpy, self, &mut name) != 0 && !name.is_null() {
+                let title = CStr::from_ptr(name).to_string_lossy().into_owned();
+                xlib::XFree(name as *mut _);
+                return title;
+            }
+        }
+        String::new()
+    }
+
+    /* WM_CLASS as (instance, class) */
+    fn get_class(self, state: &mut state::State) -> (String, String) {
+        let mut hint = xlib::XClassHint { res_name: ptr::null_mut(), res_class: ptr::null_mut() };
+        if unsafe { xlib::XGetClassHint(state.dpy, self, &mut hint) } == 0 { return (String::new(), String::new()) }
+        let take = |s: *mut i8| {
+            if s.is_null() { return String::new() }
+            let string = unsafe { CStr::from_ptr(s).to_string_lossy().into_owned() };
+            unsafe { xlib::XFree(s as *mut _) };
+            string
+        };
+        (take(hint.res_name), take(hint.res_class))
+    }
+
+    fn get_role(self, state: &mut state::State) -> String {
+        let wm_window_role = state.atom("WM_WINDOW_ROLE");
+        text_property(state, self, wm_window_role, xlib::XA_STRING).unwrap_or_default()
+    }
+
+    /* _NET_WM_WINDOW_TYPE without the prefix and lowercase, e.g. "dialog" */
+    fn get_window_type(self, state: &mut state::State) -> Option<String> {
+        let net_wm_window_type = state.atom("_NET_WM_WINDOW_TYPE");
+        let atom = *atom_property(state, self, net_wm_window_type).first()?;
+        unsafe {
+            let name = xlib::XGetAtomName(state.dpy, atom);
+            if name.is_null() { return None }
+            let string = CStr::from_ptr(name).to_string_lossy().into_owned();
+            xlib::XFree(name as *mut _);
+            Some(string.trim_start_matches("_NET_WM_WINDOW_TYPE_").to_lowercase())
+        }
+    }
+
+    fn outer(self, state: &mut state::State) -> xlib::Window {
+        state.decor.frames.get(&self).map(|f| f.window).unwrap_or(self)
+    }
 }
 
+fn raw_property(state: &mut state::State, window: xlib::Window, property: xlib::Atom, ty: xlib::Atom) -> Option<(i32, u64, *mut u8)> {
+    let mut actual_type: xlib::Atom = 0;
+    let mut actual_format: i32 = 0;
+    let mut nitems: u64 = 0;
+    let mut bytes_after: u64 = 0;
+    let mut prop: *mut u8 = ptr::null_mut();
+    let status = unsafe { xlib::XGetWindowProperty(state.dpy, window, property, 0, 1024, xlib::False, ty,
+        &mut actual_type, &mut actual_format, &mut nitems, &mut bytes_after, &mut prop) };
+    if status != 0 || prop.is_null() { return None }
+    Some((actual_format, nitems, prop))
+}
+
+fn text_property(state: &mut state::State, window: xlib::Window, property: xlib::Atom, ty: xlib::Atom) -> Option<String> {
+    let (_, nitems, prop) = raw_property(state, window, property, ty)?;
+    let text = unsafe { String::from_utf8_lossy(slice::from_raw_parts(prop, nitems as usize)).into_owned() };
+    unsafe { xlib::XFree(prop as *mut _) };
+    Some(text)
+}
+
+fn atom_property(state: &mut state::State, window: xlib::Window, property: xlib::Atom) -> Vec<xlib::Atom> {
+    match raw_property(state, window, property, xlib::XA_ATOM) {
+        Some((32, nitems, prop)) => {
+            /* format 32 properties come back as longs */
+            let atoms = unsafe { slice::from_raw_parts(prop as *const xlib::Atom, nitems as usize).to_vec() };
+            unsafe { xlib::XFree(prop as *mut _) };
+            atoms
+        },
+        Some((_, _, prop)) => {
+            unsafe { xlib::XFree(prop as *mut _) };
+            Vec::new()
+        },
+        None => Vec::new()
+    }
+}
@@ -0,0 +1,117 @@
+/* sends one command to a running xroagwem, e.g. `xroagwem-msg goto_workspace 2` or `xroagwem-msg spawn alacritty -e htop` */
+/* exits 0 if the wm ran it, 1 if it refused, 2 on bad usage and 3 if the wm can't be reached                            */
+/* `xroagwem-msg subscribe [event...]` keeps printing events until the wm goes away                                       */
+
+use std::env;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::process::exit;
+
+use serde_json::Value;
+
+mod socket;
+
+const EXIT_FAILED: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_UNREACHABLE: i32 = 3;
+
+fn usage() -> ! {
+    eprintln!("usage: xroagwem-msg [--json] <command> [argument...]");
+    eprintln!("       xroagwem-msg [--json] '<json request>'");
+    eprintln!("commands:");
+    for (name, field) in socket::COMMANDS {
+        match field {
+            Some(field) => eprintln!("  {} <{}>", name, field),
+            None => eprintln!("  {}", name)
+        }
+    }
+    exit(EXIT_USAGE);
+}
+
+/* the reply's fields other than success, one per line */
+fn print_human(reply: &Value) {
+    if let Some(fields) = reply.as_object() {
+        for (key, value) in fields.iter().filter(|(key, _)| *key != "success" && *key != "error") {
+            match value {
+                Value::String(text) => println!("{}: {}", key, text),
+                _ => println!("{}: {}", key, serde_json::to_string_pretty(value).unwrap_or_default())
+            }
+        }
+    }
+}
+
+/* `<event> <the other fields>` on one line */
+fn print_event(line: &str) {
+    let mut event: Value = serde_json::from_str(line).unwrap_or(Value::Null);
+    let name = event.as_object_mut().and_then(|fields| fields.remove("event"));
+    match name {
+        Some(Value::String(name)) => println!("{} {}", name, event),
+        _ => print!("{}", line)
+    }
+}
+
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let json = args.first().is_some_and(|arg| arg == "--json");
+    if json { args.remove(0); }
+    if args.is_empty() || args[0] == "--help" || args[0] == "-h" { usage(); }
+
+    let request = if args[0].starts_with('{') {
+        args.join(" ")
+    } else {
+        match socket::request(&args[0], &args[1..]) {
+            Ok(request) => request.to_string(),
+            Err(err) => {
+                eprintln!("xroagwem-msg: {}", err);
+                exit(EXIT_USAGE);
+            }
+        }
+    };
+
+    let path = socket::socket_path();
+    let mut stream = match UnixStream::connect(&path) {
+        Ok(stream) => stream,
+        Err(err) => {
+            eprintln!("xroagwem-msg: cannot connect to {}: {}", path.display(), err);
+            exit(EXIT_UNREACHABLE);
+        }
+    };
+    if let Err(err) = writeln!(stream, "{}", request) {
+        eprintln!("xroagwem-msg: cannot send the request: {}", err);
+        exit(EXIT_UNREACHABLE);
+    }
+
+    let mut reader = BufReader::new(&stream);
+    let mut line = String::new();
+    if reader.read_line(&mut line).unwrap_or(0) == 0 {
+        eprintln!("xroagwem-msg: no reply from xroagwem");
+        exit(EXIT_UNREACHABLE);
+    }
+    let reply: Value = serde_json::from_str(&line).unwrap_or(Value::Null);
+    let success = reply.get("success").and_then(Value::as_bool).unwrap_or(false);
+
+    if json {
+        print!("{}", line);
+    } else if success {
+        print_human(&reply);
+    } else {
+        let error = reply.get("error").and_then(Value::as_str).unwrap_or("malformed reply");
+        eprintln!("xroagwem-msg: {}", error);
+    }
+    if !success { exit(EXIT_FAILED); }
+
+    let subscribed = serde_json::from_str::<Value>(&request).ok()
+        .is_some_and(|request| request.get("command").and_then(Value::as_str) == Some("subscribe"));
+    if !subscribed { exit(0); }
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).unwrap_or(0) == 0 { break }
+        if json {
+            print!("{}", line);
+        } else {
+            print_event(&line);
+        }
+        let _ = std::io::stdout().flush();
+    }
+    exit(EXIT_UNREACHABLE);
+}
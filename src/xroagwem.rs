@@ -1,8 +1,6 @@
 
-use config::WIDGET_REFRESH;
-use widgets::widget_refresh;
 use x11::xlib::{self, XNextEvent};
-use std::{env, mem, process::exit, ptr, thread};
+use std::{mem, process::exit, ptr};
 
 mod init;
 mod error;
@@ -13,15 +11,48 @@ mod config;
 mod style;
 mod util;
 mod widgets;
+mod decor;
+mod layout;
+mod rules;
+mod scratchpad;
+mod minimise;
+mod stats;
+mod script;
+mod tray;
+mod bar;
+mod ipc;
+mod socket;
+mod mode;
 
+/* handles queued events, then sleeps on the connection until either more arrive, a widget is due, a widget thread wakes us or an ipc client talks */
 pub fn loop_poll_events(state: &mut state::State){
     let mut ev : xlib::XEvent = unsafe { mem::zeroed() };
+    let fd = unsafe { xlib::XConnectionNumber(state.dpy) };
     unsafe { xlib::XSync(state.dpy, xlib::False); }
-    while(unsafe { XNextEvent(state.dpy, &mut ev) } == 0) { event::handle(state, ev); }
+    loop {
+        while unsafe { xlib::XPending(state.dpy) } > 0 {
+            unsafe { XNextEvent(state.dpy, &mut ev) };
+            event::handle(state, ev);
+        }
+        let timeout = match state.widget_timeout() {
+            /* rounded up so the widget is due by the time poll returns */
+            Some(timeout) => timeout.as_micros().div_ceil(1000).min(i32::MAX as u128) as i32,
+            None => -1
+        };
+        let mut pfds = vec![
+            libc::pollfd { fd, events: libc::POLLIN, revents: 0 },
+            libc::pollfd { fd: widgets::wake_fd(), events: libc::POLLIN, revents: 0 }
+        ];
+        pfds.extend(state.ipc_fds());
+        unsafe { libc::poll(pfds.as_mut_ptr(), pfds.len() as libc::nfds_t, timeout) };
+        if pfds[1].revents & libc::POLLIN != 0 { widgets::drain_wake(); }
+        if pfds[2..].iter().any(|pfd| pfd.revents != 0) { state.ipc_poll(); }
+        state.tick_widgets();
+        unsafe { xlib::XFlush(state.dpy) };
+    }
 }
 
 pub fn main() {    
-    env::set_var("DISPLAY", ":1");
     match Some(unsafe{&mut(*xlib::XOpenDisplay(ptr::null()))}) {
         None => {
             println!("xroagwem: cannot initialize display!");
@@ -31,14 +62,13 @@ pub fn main() {
             init::check_other_wms(dpy);
             let mut state = init::setup(dpy);
             config::make(&mut state);
+            rules::check_rules();
             init::setup_keybindings(&mut state);
             init::setup_mousemotions(&mut state);
-            thread::spawn(|| {
-                loop {
-                    widget_refresh();
-                    thread::sleep(WIDGET_REFRESH);
-                }
-            });
+            state.start_ipc();
+            state.place_bar();
+            state.retile();
+            state.draw_widgets(widgets::Ctx::Expose);
             loop_poll_events(&mut state);
         }
     }    